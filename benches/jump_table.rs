@@ -0,0 +1,35 @@
+#[macro_use]
+extern crate criterion;
+
+use criterion::Criterion;
+
+use localc::repl::Repl;
+
+// `f` has 64 constant overloads like `example/fib.lc`. the loop calls the last case which is
+// the worst case for the linear dispatch chain.
+fn setup(jump_tables: bool) -> Repl {
+    let mut repl = Repl::new();
+    repl.runtime.jump_tables = jump_tables;
+
+    for i in 0..64 {
+        repl.run(&format!("f({}) = {}", i, i * 2)).unwrap();
+    }
+    repl.run("f(x) = x").unwrap();
+    repl.run("loop(0, acc) = acc").unwrap();
+    repl.run("loop(n, acc) = loop(n - 1, acc + f(63))").unwrap();
+
+    repl
+}
+
+// both dispatch strategies are reported in one group to compare them side by side
+fn dispatch(c: &mut Criterion) {
+    let mut group = c.benchmark_group("dispatch");
+    for (name, jump_tables) in [("linear", false), ("jump table", true)].iter() {
+        let mut repl = setup(*jump_tables);
+        group.bench_function(*name, |b| b.iter(|| repl.run("loop(1000, 0)").unwrap()));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, dispatch);
+criterion_main!(benches);
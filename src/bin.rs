@@ -3,30 +3,195 @@ extern crate ansi_term;
 use localc::*;
 
 use ansi_term::Color::*;
+use serde_json::json;
 
 use std::env;
-use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io;
+use std::process;
 
-pub fn main() {
-    env_logger::init();
-    let args = env::args();
-    let mut repl = repl::Repl::with_stdlib();
-
-    for path in args.skip(1) {
-        match File::open(path.clone()) {
-            Ok(file) => {
-                for line in BufReader::new(file).lines() {
-                    let line = line.unwrap();
-                    if line.is_empty() {
-                        continue;
-                    }
-                    repl.run(&line).expect("error in runtime script");
+const USAGE: &str = "\
+usage: localc-repl [options] [file.lc ...]
+
+runs the given scripts and exits. starts an interactive session if neither scripts nor
+expressions are given.
+
+options:
+    -e EXPR             evaluate EXPR and print the result
+    -i                  stay interactive after running scripts and expressions
+    -k, --keep-going    continue with the next statement after an error
+    --no-stdlib         do not load the standard library
+    --unit FILE         load a unit compiled by `localc-cc`
+    --format FORMAT     print results as `plain` (default) or `json`. with `json`, the result
+                        of every statement in scripts is printed and errors are json objects
+    --kernel            answer json requests on stdin after running scripts and expressions
+    -h, --help          print this message
+    -V, --version       print the version";
+
+enum Input {
+    File(String),
+    Expr(String),
+}
+
+struct Config {
+    inputs: Vec<Input>,
+    units: Vec<String>,
+    interactive: bool,
+    kernel: bool,
+    keep_going: bool,
+    stdlib: bool,
+    format: runtime::Format,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            inputs: vec![],
+            units: vec![],
+            interactive: false,
+            kernel: false,
+            keep_going: false,
+            stdlib: true,
+            format: runtime::Format::Plain,
+        }
+    }
+}
+
+enum Action {
+    Run(Config),
+    Help,
+    Version,
+}
+
+fn get_config_from_args<I>(mut args: I) -> Result<Action, String>
+where
+    I: Iterator<Item = String>,
+{
+    let mut config = Config::default();
+    let value = |args: &mut I, flag: &str| {
+        args.next()
+            .ok_or_else(|| format!("option `{}` expects a value", flag))
+    };
+
+    while let Some(arg) = args.next() {
+        match arg.as_ref() {
+            "-e" => config.inputs.push(Input::Expr(value(&mut args, "-e")?)),
+            "-i" => config.interactive = true,
+            "--kernel" => config.kernel = true,
+            "-k" | "--keep-going" => config.keep_going = true,
+            "--no-stdlib" => config.stdlib = false,
+            "--unit" => config.units.push(value(&mut args, "--unit")?),
+            "--format" => config.format = value(&mut args, "--format")?.parse()?,
+            "-h" | "--help" => return Ok(Action::Help),
+            "-V" | "--version" => return Ok(Action::Version),
+            flag if flag.starts_with('-') && flag != "-" => {
+                return Err(format!("unknown option `{}`", flag))
+            }
+            _ => config.inputs.push(Input::File(arg.clone())),
+        }
+    }
+
+    Ok(Action::Run(config))
+}
+
+// errors are written as json objects if results are
+fn report(format: runtime::Format, kind: &str, message: &str) {
+    match format {
+        runtime::Format::Json => eprintln!("{}", json!({"kind": kind, "message": message})),
+        runtime::Format::Plain => eprintln!("{}", Red.paint(message)),
+    }
+}
+
+fn report_script_error(format: runtime::Format, err: &repl::script::ScriptError) {
+    match format {
+        runtime::Format::Json => eprintln!("{}", err.to_json()),
+        runtime::Format::Plain => eprintln!("{}", Red.paint(err.to_string())),
+    }
+}
+
+fn run(config: Config) -> bool {
+    let mut repl = if config.stdlib {
+        repl::Repl::with_stdlib()
+    } else {
+        repl::Repl::new()
+    };
+    repl.pretty.format = config.format;
+
+    let mut failed = false;
+
+    for path in config.units.iter() {
+        if let Err(err) = repl.load_unit_file(path) {
+            report(config.format, "unit", &err);
+            return false;
+        }
+    }
+
+    let show = |repl: &repl::Repl, result: &lovm::Value| println!("{}", repl.format(result));
+    for input in config.inputs.iter() {
+        let result = match input {
+            // results of scripts are only needed for further processing
+            Input::File(path) if config.format == runtime::Format::Json => {
+                repl.run_file_with(path, config.keep_going, show)
+            }
+            Input::File(path) => repl.run_file(path, config.keep_going),
+            Input::Expr(src) => Ok(repl.run_script_with("-e", src, config.keep_going, show)),
+        };
+        match result {
+            Ok(errors) => {
+                for err in errors.iter() {
+                    report_script_error(config.format, err);
                 }
+                failed |= !errors.is_empty();
+            }
+            Err(err) => {
+                report(config.format, "io", &err);
+                failed = true;
             }
-            _ => println!("could not open file `{}`", Red.paint(path)),
         }
+        if failed && !config.keep_going {
+            break;
+        }
+    }
+
+    if config.kernel {
+        let stdin = io::BufReader::new(io::stdin());
+        let stdout = io::stdout();
+        let mut kernel = kernel::Kernel::new(repl);
+        if let Err(err) = kernel.serve(stdin, &mut stdout.lock()) {
+            report(config.format, "io", &err.to_string());
+            return false;
+        }
+        return !failed;
     }
 
-    repl.repeat().unwrap();
+    if config.inputs.is_empty() || config.interactive {
+        repl.repeat().unwrap();
+    }
+
+    !failed
+}
+
+pub fn main() {
+    env_logger::init();
+
+    let config = match get_config_from_args(env::args().skip(1)) {
+        Ok(Action::Run(config)) => config,
+        Ok(Action::Help) => {
+            println!("{}", USAGE);
+            return;
+        }
+        Ok(Action::Version) => {
+            println!("localc-repl {}", env!("CARGO_PKG_VERSION"));
+            return;
+        }
+        Err(err) => {
+            eprintln!("{}", Red.paint(err));
+            eprintln!("try `localc-repl --help`");
+            process::exit(2);
+        }
+    };
+
+    // the exit code tells if all scripts and expressions succeeded
+    if !run(config) {
+        process::exit(1);
+    }
 }
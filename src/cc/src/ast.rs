@@ -1,5 +1,9 @@
 use super::*;
 
+use std::collections::HashMap;
+use std::convert::{TryFrom, TryInto};
+use std::hash::Hash;
+
 pub fn numeric(raw: &str) -> NumType {
     let re = regex::Regex::new(r"[+-]?\d+(\.\d+)?").unwrap();
     let groups = re.captures(raw).unwrap();
@@ -10,6 +14,22 @@ pub fn numeric(raw: &str) -> NumType {
     }
 }
 
+// content of a string token without its quotes. quotes inside of it are doubled.
+pub fn unquote(token: &str) -> lovm::Str {
+    token[1..token.len() - 1].replace("''", "'").as_str().into()
+}
+
+// non-finite rationals have no literal and are written as the division producing them
+pub fn non_finite(n: f64) -> &'static str {
+    if n.is_nan() {
+        "0.0 / 0"
+    } else if n.is_sign_positive() {
+        "1.0 / 0"
+    } else {
+        "-1.0 / 0"
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub enum NumType {
     Natural(i64),
@@ -81,8 +101,14 @@ impl From<Expr> for lovm::gen::OpValue {
         use lovm::gen::*;
 
         match v {
-            Expr::Value(Value::Set(set)) => unimplemented!(),
-            Expr::Value(v) => Self::from(v),
+            // nested tuples and sets are built by lovm from their items
+            Expr::Value(Value::Tuple(tuple)) => Self::from(tuple),
+            Expr::Value(Value::Set(set)) => Self::from(set),
+            Expr::Value(Value::Numeric(NumType::Natural(n))) => Self::from(n),
+            Expr::Value(Value::Numeric(NumType::Rational(n))) => Self::from(n),
+            Expr::Value(Value::Logical(t)) => Self::from(t),
+            Expr::Value(Value::Str(s)) => Self::from(s.as_ref()),
+            Expr::Value(Value::Nil) => unimplemented!(),
             Expr::Ref(name) => OpValue::Operation(Operation::push().var(name).end()),
             // TODO: add these
             Expr::Comp(op, lhs, rhs) => {
@@ -121,14 +147,27 @@ impl From<Expr> for lovm::gen::OpValue {
     }
 }
 
-impl From<Value> for lovm::Value {
-    fn from(v: Value) -> Self {
+impl From<NumType> for lovm::Value {
+    fn from(n: NumType) -> Self {
+        match n {
+            NumType::Natural(n) => lovm::Value::I64(n),
+            NumType::Rational(n) => lovm::Value::F64(n),
+        }
+    }
+}
+
+// tuples and sets live in the object pool of a vm and `Nil` has no lovm equivalent
+impl TryFrom<Value> for lovm::Value {
+    type Error = String;
+    fn try_from(v: Value) -> Result<Self, Self::Error> {
         match v {
-            Value::Numeric(NumType::Natural(n)) => lovm::Value::I64(n),
-            Value::Numeric(NumType::Rational(n)) => lovm::Value::F64(n),
-            Value::Logical(t) => lovm::Value::T(t),
-            Value::Str(s) => lovm::Value::Str(s),
-            _ => panic!("not expected lovm value `{:?}`", v),
+            Value::Numeric(n) => Ok(n.into()),
+            Value::Logical(t) => Ok(lovm::Value::T(t)),
+            Value::Str(s) => Ok(lovm::Value::Str(s)),
+            other => Err(format!(
+                "expected number, logical or string, got `{:?}`",
+                other
+            )),
         }
     }
 }
@@ -165,9 +204,149 @@ impl From<&Value> for LogType {
     }
 }
 
-impl From<&str> for Expr {
-    fn from(from: &str) -> Self {
-        Expr::Value(Value::Str(from.into()))
+// conversions between rust and localc values. tuples and sets only contain values here;
+// converting a tuple or set that still contains unevaluated expressions fails.
+
+impl From<i64> for Value {
+    fn from(n: i64) -> Self {
+        Value::Numeric(NumType::Natural(n))
+    }
+}
+
+impl From<f64> for Value {
+    fn from(n: f64) -> Self {
+        Value::Numeric(NumType::Rational(n))
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Value::Str(s.into())
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Self {
+        Value::Str(s.as_str().into())
+    }
+}
+
+impl<T> From<Vec<T>> for Value
+where
+    T: Into<Value>,
+{
+    fn from(items: Vec<T>) -> Self {
+        Value::Tuple(
+            items
+                .into_iter()
+                .map(|item| Expr::Value(item.into()))
+                .collect(),
+        )
+    }
+}
+
+impl<K, V> From<HashMap<K, V>> for Value
+where
+    K: Into<Value>,
+    V: Into<Value>,
+{
+    fn from(entries: HashMap<K, V>) -> Self {
+        Value::Set(
+            entries
+                .into_iter()
+                .map(|(key, value)| (Some(Expr::Value(key.into())), Expr::Value(value.into())))
+                .collect(),
+        )
+    }
+}
+
+impl TryFrom<Value> for i64 {
+    type Error = String;
+    fn try_from(v: Value) -> Result<Self, Self::Error> {
+        match v {
+            Value::Numeric(NumType::Natural(n)) => Ok(n),
+            other => Err(format!("expected integer, got `{:?}`", other)),
+        }
+    }
+}
+
+impl TryFrom<Value> for f64 {
+    type Error = String;
+    fn try_from(v: Value) -> Result<Self, Self::Error> {
+        match v {
+            Value::Numeric(NumType::Natural(n)) => Ok(n as f64),
+            Value::Numeric(NumType::Rational(n)) => Ok(n),
+            other => Err(format!("expected number, got `{:?}`", other)),
+        }
+    }
+}
+
+impl TryFrom<Value> for bool {
+    type Error = String;
+    fn try_from(v: Value) -> Result<Self, Self::Error> {
+        match v {
+            Value::Logical(l) => Ok(l),
+            other => Err(format!("expected logical, got `{:?}`", other)),
+        }
+    }
+}
+
+impl TryFrom<Value> for String {
+    type Error = String;
+    fn try_from(v: Value) -> Result<Self, Self::Error> {
+        match v {
+            Value::Str(s) => Ok(s.to_string()),
+            other => Err(format!("expected string, got `{:?}`", other)),
+        }
+    }
+}
+
+impl<T> TryFrom<Value> for Vec<T>
+where
+    T: TryFrom<Value, Error = String>,
+{
+    type Error = String;
+    fn try_from(v: Value) -> Result<Self, Self::Error> {
+        match v {
+            Value::Tuple(items) => items
+                .into_iter()
+                .map(|item| T::try_from(item.try_into()?))
+                .collect(),
+            other => Err(format!("expected tuple, got `{:?}`", other)),
+        }
+    }
+}
+
+impl<K, V> TryFrom<Value> for HashMap<K, V>
+where
+    K: TryFrom<Value, Error = String> + Eq + Hash,
+    V: TryFrom<Value, Error = String>,
+{
+    type Error = String;
+    fn try_from(v: Value) -> Result<Self, Self::Error> {
+        match v {
+            Value::Set(entries) => entries
+                .into_iter()
+                .map(|(key, value)| match key {
+                    Some(key) => Ok((
+                        K::try_from(key.try_into()?)?,
+                        V::try_from(value.try_into()?)?,
+                    )),
+                    _ => Err("set entry has no key".to_string()),
+                })
+                .collect(),
+            other => Err(format!("expected set, got `{:?}`", other)),
+        }
+    }
+}
+
+impl TryFrom<Expr> for Value {
+    type Error = String;
+    fn try_from(expr: Expr) -> Result<Self, Self::Error> {
+        match expr {
+            Expr::Value(v) => Ok(v),
+            other => Err(format!("expected value, got `{:?}`", other)),
+        }
     }
 }
 
@@ -180,22 +359,149 @@ where
     }
 }
 
+// `Display` emits localc source. binary operators are only parenthesized where the grammar's
+// precedence tiers require it, so parsing the output yields the same expression.
+
 impl std::fmt::Display for Value {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
         match self {
-            Value::Numeric(NumType::Natural(n)) => write!(f, "{}", n).unwrap(),
-            Value::Numeric(NumType::Rational(n)) => write!(f, "{}", n).unwrap(),
-            Value::Logical(l) => write!(f, "{}", l).unwrap(),
-            _ => unreachable!(),
+            Value::Nil => write!(f, "Nil"),
+            Value::Numeric(NumType::Natural(n)) => write!(f, "{}", n),
+            // parenthesized to be read as one operand
+            Value::Numeric(NumType::Rational(n)) if !n.is_finite() => {
+                write!(f, "({})", non_finite(*n))
+            }
+            Value::Numeric(NumType::Rational(n)) => {
+                // rationals keep their decimal point
+                let repr = n.to_string();
+                if repr.contains('.') {
+                    write!(f, "{}", repr)
+                } else {
+                    write!(f, "{}.0", repr)
+                }
+            }
+            Value::Logical(l) => write!(f, "{}", l),
+            Value::Str(s) => write!(f, "'{}'", s.to_string().replace('\'', "''")),
+            // a single item needs a trailing comma to not be read as parenthesized expression
+            Value::Tuple(items) if items.len() == 1 => write!(f, "({},)", items[0]),
+            Value::Tuple(items) => {
+                write!(f, "(")?;
+                write_list(f, items.iter())?;
+                write!(f, ")")
+            }
+            Value::Set(items) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in items.iter().enumerate() {
+                    if 0 < i {
+                        write!(f, ", ")?;
+                    }
+                    if let Some(key) = key {
+                        write_operand(f, key, Operator::Store.tier())?;
+                        write!(f, " = ")?;
+                    }
+                    write_operand(f, value, Operator::Store.tier() + 1)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+impl Operator {
+    // precedence tier in the grammar. operators of higher tiers bind stronger; all operators
+    // are left associative.
+    pub fn tier(&self) -> usize {
+        match self {
+            Operator::Store => 0,
+            Operator::Or => 1,
+            Operator::And => 2,
+            Operator::Eq
+            | Operator::Ne
+            | Operator::Ge
+            | Operator::Gt
+            | Operator::Le
+            | Operator::Lt => 3,
+            Operator::Add | Operator::Sub => 4,
+            Operator::Mul | Operator::Div | Operator::Rem | Operator::Pow => 5,
+        }
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Operator::Add => "+",
+            Operator::Sub => "-",
+            Operator::Mul => "*",
+            Operator::Div => "/",
+            Operator::Pow => "^",
+            Operator::Rem => "%",
+            Operator::Eq => "==",
+            Operator::Ne => "!=",
+            Operator::Ge => ">=",
+            Operator::Gt => ">",
+            Operator::Le => "<=",
+            Operator::Lt => "<",
+            Operator::And => "&&",
+            Operator::Or => "||",
+            Operator::Store => "=",
         }
-        Ok(())
     }
 }
 
 impl std::fmt::Display for Operator {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
-        write!(f, "{:?}", self)
+        write!(f, "{}", self.symbol())
+    }
+}
+
+impl Expr {
+    // tier of the outermost operator. values, references and calls never need parentheses.
+    fn tier(&self) -> usize {
+        match self {
+            Expr::Comp(op, _, _) => op.tier(),
+            _ => usize::max_value(),
+        }
+    }
+}
+
+impl std::fmt::Display for Expr {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        match self {
+            Expr::Value(v) => write!(f, "{}", v),
+            Expr::Ref(r) => write!(f, "{}", r),
+            Expr::Comp(op, lhs, rhs) => {
+                write_operand(f, lhs, op.tier())?;
+                write!(f, " {} ", op)?;
+                write_operand(f, rhs, op.tier() + 1)
+            }
+            Expr::Func(name, args) => {
+                write!(f, "{}(", name)?;
+                write_list(f, args.iter())?;
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+// write `expr` and wrap it in parentheses if it binds weaker than `tier`
+fn write_operand(f: &mut std::fmt::Formatter, expr: &Expr, tier: usize) -> std::fmt::Result {
+    if expr.tier() < tier {
+        write!(f, "({})", expr)
+    } else {
+        write!(f, "{}", expr)
+    }
+}
+
+fn write_list<'a>(
+    f: &mut std::fmt::Formatter,
+    items: impl Iterator<Item = &'a Expr>,
+) -> std::fmt::Result {
+    for (i, item) in items.enumerate() {
+        if 0 < i {
+            write!(f, ", ")?;
+        }
+        write!(f, "{}", item)?;
     }
+    Ok(())
 }
 
 impl std::fmt::Debug for Expr {
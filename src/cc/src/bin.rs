@@ -1,18 +1,53 @@
+#![feature(box_patterns)]
+
+use localc_cc_lib::ast::*;
 use localc_cc_lib::*;
 
 use lovm::*;
 
 use std::env;
 use std::fs::File;
-use std::io::{BufRead, BufReader, Write};
+use std::io::Write;
+use std::process;
+
+const USAGE: &str = "\
+usage: localc-cc [options] file.lc ...
+
+input files named `-` are read from stdin. bytecode can only be emitted for function
+definitions.
+
+options:
+    -o FILE             write output to FILE (default: out.lcc for bytecode, stdout otherwise)
+    --emit KIND         emit `bytecode` (default), `asm` or `ast`
+    -v                  print progress information
+    -h, --help          print this message
+    -V, --version       print the version";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Emit {
+    Bytecode,
+    Asm,
+    Ast,
+}
 
-#[derive(Default)]
 struct Config {
     verbose: bool,
+    emit: Emit,
     input_files: Vec<String>,
     output_file: Option<String>,
 }
 
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            verbose: false,
+            emit: Emit::Bytecode,
+            input_files: vec![],
+            output_file: None,
+        }
+    }
+}
+
 impl Config {
     fn output_file(&self) -> String {
         match self.output_file.as_ref() {
@@ -20,38 +55,175 @@ impl Config {
             _ => "out.lcc".to_string(),
         }
     }
+
+    fn log(&self, msg: &str) {
+        if self.verbose {
+            eprintln!("{}", msg);
+        }
+    }
+}
+
+enum Action {
+    Compile(Config),
+    Help,
+    Version,
 }
 
-fn get_config_from_args() -> Config {
-    let mut args = env::args().skip(1);
+fn get_config_from_args<I>(mut args: I) -> Result<Action, String>
+where
+    I: Iterator<Item = String>,
+{
     let mut config = Config::default();
+    let value = |args: &mut I, flag: &str| {
+        args.next()
+            .ok_or_else(|| format!("option `{}` expects a value", flag))
+    };
 
     while let Some(arg) = args.next() {
         match arg.as_ref() {
             "-v" => config.verbose = true,
-            "-o" => config.output_file = Some(args.next().expect("no output file")),
-            _ => config.input_files.push(arg),
+            "-o" => config.output_file = Some(value(&mut args, "-o")?),
+            "--emit" => {
+                config.emit = match value(&mut args, "--emit")?.as_ref() {
+                    "bytecode" => Emit::Bytecode,
+                    "asm" => Emit::Asm,
+                    "ast" => Emit::Ast,
+                    other => {
+                        return Err(format!(
+                            "unknown output `{}`, expected `bytecode`, `asm` or `ast`",
+                            other
+                        ))
+                    }
+                }
+            }
+            "-h" | "--help" => return Ok(Action::Help),
+            "-" => config.input_files.push(arg.clone()),
+            "-V" | "--version" => return Ok(Action::Version),
+            flag if flag.starts_with('-') => return Err(format!("unknown option `{}`", flag)),
+            _ => config.input_files.push(arg.clone()),
         }
     }
 
-    config
+    if config.input_files.is_empty() {
+        return Err("no input files given".to_string());
+    }
+
+    Ok(Action::Compile(config))
+}
+
+fn write_file(file: &str, content: &[u8]) -> Result<(), String> {
+    File::create(file)
+        .and_then(|mut f| f.write_all(content))
+        .map_err(|err| format!("could not write `{}`: {}", file, err))
 }
 
-fn write_file(file: &str, unit: Unit) {
-    match File::create(file.clone()) {
-        Ok(mut file) => file.write_all(&unit.serialize().unwrap()).unwrap(),
-        _ => panic!("could not open file `{}`", file),
+// write to the output file if one was given, otherwise to stdout
+fn emit_text(config: &Config, text: &str) -> Result<(), String> {
+    match config.output_file.as_ref() {
+        Some(file) => write_file(file, text.as_bytes()),
+        _ => {
+            print!("{}", text);
+            Ok(())
+        }
     }
 }
 
-pub fn main() {
-    let config = get_config_from_args();
+// parse every statement of the input files and pass it to `f` together with its source.
+// statements are split like `compiler::compile_files` does.
+fn each_statement<F>(config: &Config, mut f: F) -> Result<(), String>
+where
+    F: FnMut(&str, Expr) -> Result<(), String>,
+{
+    let parser = expr::ExprParser::new();
 
-    if config.input_files.is_empty() {
-        panic!("no input files given");
+    for file in config.input_files.iter() {
+        config.log(&format!("parsing `{}`", file));
+        let content = compiler::read_source(file)?;
+        for (line, code) in formatter::statements(&content).into_iter() {
+            let ast = parser
+                .parse(&code)
+                .map_err(|err| format!("{}:{}: {:?}", file, line, err))?;
+            f(&code, ast).map_err(|err| format!("{}:{}: {}", file, line, err))?;
+        }
     }
 
-    let co = compiler::compile_files(&config.input_files).expect("could not compile code");
+    Ok(())
+}
+
+fn emit_ast(config: &Config) -> Result<(), String> {
+    let mut text = String::new();
+    each_statement(config, |_, ast| {
+        text.push_str(&format!("{}\n", ast));
+        Ok(())
+    })?;
+    emit_text(config, &text)
+}
 
-    write_file(&config.output_file(), co);
+// listing of every statement. function definitions show the code of the overload only; the
+// dispatch between overloads is generated by the runtime.
+fn emit_asm(config: &Config) -> Result<(), String> {
+    let mut text = String::new();
+    let ctx = optimize::FoldContext::new();
+    each_statement(config, |line, ast| {
+        let co = match ast {
+            Expr::Comp(Operator::Store, box Expr::Func(name, params), body) => {
+                let body = optimize::fold_with_params(&body, &ctx, &params);
+                let co = compiler::compile_overload(&body, &params, &name)?;
+                co.build(true)
+                    .map_err(|_| format!("could not build `{}`", name))?
+            }
+            Expr::Comp(Operator::Store, box Expr::Ref(_), body) => {
+                compiler::compile_expr(&optimize::fold(&body, &ctx))?
+            }
+            other => compiler::compile_expr(&optimize::fold(&other, &ctx))?,
+        };
+        text.push_str(&format!(
+            "; {}\n{}\n",
+            line.trim(),
+            disasm::disassemble(&co)
+        ));
+        Ok(())
+    })?;
+    emit_text(config, &text)
+}
+
+fn emit_bytecode(config: &Config) -> Result<(), String> {
+    for file in config.input_files.iter() {
+        config.log(&format!("compiling `{}`", file));
+    }
+    let unit = compiler::compile_files(&config.input_files)?;
+    let output = config.output_file();
+    write_file(&output, &unit.serialize().unwrap())?;
+    config.log(&format!("wrote `{}`", output));
+    Ok(())
+}
+
+pub fn main() {
+    let config = match get_config_from_args(env::args().skip(1)) {
+        Ok(Action::Compile(config)) => config,
+        Ok(Action::Help) => {
+            println!("{}", USAGE);
+            return;
+        }
+        Ok(Action::Version) => {
+            println!("localc-cc {}", env!("CARGO_PKG_VERSION"));
+            return;
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            eprintln!("try `localc-cc --help`");
+            process::exit(2);
+        }
+    };
+
+    let result = match config.emit {
+        Emit::Bytecode => emit_bytecode(&config),
+        Emit::Asm => emit_asm(&config),
+        Emit::Ast => emit_ast(&config),
+    };
+
+    if let Err(err) = result {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
 }
@@ -5,60 +5,119 @@ use lovm::*;
 
 pub type CompileResult = Result<CodeObject, String>;
 
-pub fn compile_files(files: &Vec<String>) -> Result<Unit, String> {
-    use std::fs::File;
+// read the source of `file`. `-` reads from stdin.
+pub fn read_source(file: &str) -> Result<String, String> {
     use std::io::Read;
 
-    let mut parser = ExprParser::new();
-    let mut unit = UnitBuilder::new();
+    let mut content = String::new();
+    let result = match file {
+        "-" => std::io::stdin().read_to_string(&mut content),
+        _ => std::fs::File::open(file).and_then(|mut handle| handle.read_to_string(&mut content)),
+    };
+    result.map_err(|err| format!("could not read `{}`: {}", file, err))?;
+    Ok(content)
+}
+
+// compile the function definitions of `files` into a unit. every function becomes one code
+// object built by `Function::build`, which dispatches between the overloads through linear
+// chains.
+pub fn compile_files(files: &Vec<String>) -> Result<Unit, String> {
+    let parser = ExprParser::new();
+    let ctx = FoldContext::new();
+    let mut functions: Vec<(String, Function)> = vec![];
 
     for file in files.iter() {
-        match File::open(file) {
-            Ok(mut file) => {
-                let mut content = String::new();
-                file.read_to_string(&mut content).unwrap();
-                for line in content.lines().filter(|line| !line.is_empty()) {
-                    let ast = parser.parse(line).expect("parse error");
-                    //match ast {
-                    //    Expr::Comp(Operator::Store, lhs, rhs) => match lhs {
-                    //        box Expr::Func(name, params) => self.store_fun(name, params, rhs),
-                    //        box Expr::Ref(name) => self.store_var(name, rhs),
-                    //        _ => Err("assignment not allowed".to_string()),
-                    //    },
-                    //    _ => {
-                    //        // TODO: if this returns a reference to an temporary object; drop it to save memory
-                    //        // no storage location given: execute directly
-                    //        self.run(&compiler::compile_expr(expr)?)
-                    //    }
-                    //}
+        let content = read_source(file)?;
+        for (line, code) in formatter::statements(&content).into_iter() {
+            let at = |err: String| format!("{}:{}: {}", file, line, err);
+            let ast = parser
+                .parse(&code)
+                .map_err(|err| at(format!("{:?}", err)))?;
+            let definition = match ast {
+                Expr::Comp(Operator::Store, lhs, body) => match *lhs {
+                    Expr::Func(name, params) => Some((name, params, body)),
+                    _ => None,
+                },
+                _ => None,
+            };
+            let (name, params, body) = match definition {
+                Some(definition) => definition,
+                _ => return Err(at("only function definitions can be compiled".to_string())),
+            };
+
+            let body = fold_with_params(&body, &ctx, &params);
+            let overload = compile_overload(&body, &params, &name).map_err(at)?;
+            let idx = match functions.iter().position(|(other, _)| *other == name) {
+                Some(idx) => idx,
+                _ => {
+                    functions.push((name, Function::new()));
+                    functions.len() - 1
                 }
-            }
-            _ => return Err(format!("could not open file `{}`", file)),
+            };
+            // a later definition replaces the overload with the same pattern
+            functions[idx].1.overload(params, overload);
         }
     }
 
-    Ok(unit.build().unwrap())
+    let mut unit = UnitBuilder::new();
+    for (name, function) in functions.into_iter() {
+        let co = function
+            .build()
+            .map_err(|_| format!("could not build function `{}`", name))?;
+        unit.set(&name, co);
+    }
+
+    unit.build().map_err(|_| "could not build unit".to_string())
 }
 
 pub fn compile_str(s: &str) -> CompileResult {
     let expr = ExprParser::new().parse(s.as_ref()).unwrap();
-    compile_expr(&expr)
+    compile_expr(&fold(&expr, &FoldContext::new()))
 }
 
-pub fn compile_with_params_lazy(ast: &Expr, params: &TupleType) -> Result<CodeBuilder, String> {
-    let params = params
+// variables of an overload signature. constants are matched by the dispatch table.
+fn param_names(params: &TupleType) -> Vec<String> {
+    params
         .iter()
         .filter_map(|param| match param {
             Expr::Ref(n) => Some(n.clone()),
             _ => None,
         })
-        .collect::<Vec<_>>();
-    let mut func = CodeBuilder::new().with_params(params);
+        .collect()
+}
+
+// the `compile_*` functions expect `ast` to be folded already (see `optimize::fold`)
+pub fn compile_with_params_lazy(ast: &Expr, params: &TupleType) -> Result<CodeBuilder, String> {
+    let mut func = CodeBuilder::new().with_params(param_names(params));
     let mut op_stack = vec![];
     compile_deep(&mut func, &mut op_stack, ast)?;
     Ok(func)
 }
 
+// compile an overload of function `fname`. if the body is a call to `fname` itself, the
+// arguments are pushed and control is passed back to the functions dispatch table instead of
+// emitting a `call`. `Function::build` runs the dispatch table inside a loop. localc has no
+// conditional expressions, so a direct self call is the only call in tail position.
+pub fn compile_overload(
+    ast: &Expr,
+    params: &TupleType,
+    fname: &str,
+) -> Result<CodeBuilder, String> {
+    match ast {
+        Expr::Func(name, args) if name == fname => {
+            let mut func = CodeBuilder::new().with_params(param_names(params));
+            // calling order does not need `.rev()`
+            for arg in args.iter() {
+                compile_deep(&mut func, &mut vec![], arg)?;
+            }
+            func.step(Operation::push().op(args.len()).end());
+            func.step(Operation::cont());
+            Ok(func)
+        }
+        _ => compile_with_params_lazy(ast, params),
+    }
+}
+
 pub fn compile_with_params(ast: &Expr, params: &TupleType) -> CompileResult {
     let func = compile_with_params_lazy(ast, params)?;
     let func = func.build(true).unwrap();
@@ -69,9 +128,11 @@ pub fn compile_expr(ast: &Expr) -> CompileResult {
     let mut func = CodeBuilder::new();
     let mut op_stack = vec![];
     compile_deep(&mut func, &mut op_stack, ast)?;
-    func.debug();
-    let func = func.build(true).unwrap();
-    Ok(func.into())
+    let func: CodeObject = func.build(true).unwrap().into();
+    if log_enabled!(log::Level::Trace) {
+        trace!("compiled `{:?}`\n{}", ast, disasm::disassemble(&func));
+    }
+    Ok(func)
 }
 
 fn compile_deep(
@@ -0,0 +1,233 @@
+use super::*;
+
+use crate::ast::Value;
+
+// symbolic differentiation of `Expr` trees. the result is not simplified apart from dropping
+// terms that are obviously zero, so `simplify` should be applied before printing it.
+
+pub type DiffResult = Result<Expr, String>;
+
+// differentiate `expr` with respect to the variable `var`
+pub fn diff(expr: &Expr, var: &str) -> DiffResult {
+    diff_with(expr, var, &|_, _| None)
+}
+
+// like `diff` but calls to unknown functions are inlined using `resolve`. `resolve` takes the
+// function name and argument count and returns the parameter names and body of the function.
+pub fn diff_with<F>(expr: &Expr, var: &str, resolve: &F) -> DiffResult
+where
+    F: Fn(&str, usize) -> Option<(Vec<String>, Expr)>,
+{
+    derive(expr, var, resolve, &mut vec![])
+}
+
+// `inlining` holds the names of the functions whose bodies are currently being differentiated
+fn derive<F>(expr: &Expr, var: &str, resolve: &F, inlining: &mut Vec<String>) -> DiffResult
+where
+    F: Fn(&str, usize) -> Option<(Vec<String>, Expr)>,
+{
+    if !depends_on(expr, var) {
+        return Ok(num(0));
+    }
+
+    match expr {
+        Expr::Ref(_) => Ok(num(1)),
+        Expr::Comp(op, lhs, rhs) => {
+            let dl = derive(lhs, var, resolve, inlining)?;
+            let dr = derive(rhs, var, resolve, inlining)?;
+            let (lhs, rhs) = (*lhs.clone(), *rhs.clone());
+            match op {
+                Operator::Add => Ok(add(dl, dr)),
+                Operator::Sub => Ok(sub(dl, dr)),
+                Operator::Mul => Ok(add(mul(dl, rhs.clone()), mul(lhs, dr))),
+                Operator::Div => Ok(div(
+                    sub(mul(dl, rhs.clone()), mul(lhs, dr)),
+                    pow(rhs, num(2)),
+                )),
+                Operator::Pow if !depends_on(&rhs, var) => {
+                    Ok(mul(mul(rhs.clone(), pow(lhs, sub(rhs, num(1)))), dl))
+                }
+                Operator::Pow if !depends_on(&lhs, var) => {
+                    Ok(mul(mul(pow(lhs.clone(), rhs), call("ln", lhs)), dr))
+                }
+                // d(u ^ v) = u ^ v * (v' * ln(u) + v * u' / u)
+                Operator::Pow => Ok(mul(
+                    pow(lhs.clone(), rhs.clone()),
+                    add(mul(dr, call("ln", lhs.clone())), div(mul(rhs, dl), lhs)),
+                )),
+                _ => Err(format!("cannot differentiate operator `{}`", op)),
+            }
+        }
+        Expr::Func(name, args) if args.len() == 1 => {
+            let u = args[0].clone();
+            let du = derive(&u, var, resolve, inlining)?;
+            let outer = match name.as_ref() {
+                "sqrt" => div(num(1), mul(num(2), call("sqrt", u))),
+                "ln" => div(num(1), u),
+                "log" => div(num(1), mul(u, call("ln", num(10)))),
+                "log2" => div(num(1), mul(u, call("ln", num(2)))),
+                "exp" => call("exp", u),
+                "sin" => call("cos", u),
+                "cos" => mul(num(-1), call("sin", u)),
+                "tan" => div(num(1), pow(call("cos", u), num(2))),
+                "asin" => div(num(1), call("sqrt", sub(num(1), pow(u, num(2))))),
+                "acos" => div(num(-1), call("sqrt", sub(num(1), pow(u, num(2))))),
+                "atan" => div(num(1), add(num(1), pow(u, num(2)))),
+                "sinh" => call("cosh", u),
+                "cosh" => call("sinh", u),
+                "tanh" => div(num(1), pow(call("cosh", u), num(2))),
+                _ => return inline(name, args, var, resolve, inlining),
+            };
+            Ok(mul(outer, du))
+        }
+        Expr::Func(name, args) => inline(name, args, var, resolve, inlining),
+        Expr::Value(_) => Ok(num(0)),
+    }
+}
+
+// differentiate a call to a user-defined function by substituting its arguments into the body.
+// recursive functions cannot be inlined.
+fn inline<F>(
+    name: &str,
+    args: &[Expr],
+    var: &str,
+    resolve: &F,
+    inlining: &mut Vec<String>,
+) -> DiffResult
+where
+    F: Fn(&str, usize) -> Option<(Vec<String>, Expr)>,
+{
+    if inlining.iter().any(|other| other == name) {
+        return Err(format!(
+            "cannot differentiate recursive function `{}`",
+            name
+        ));
+    }
+    match resolve(name, args.len()) {
+        Some((params, body)) => {
+            let body = substitute(&body, &params, args);
+            inlining.push(name.to_string());
+            let result = derive(&body, var, resolve, inlining);
+            inlining.pop();
+            result
+        }
+        _ => Err(format!("cannot differentiate call to `{}`", name)),
+    }
+}
+
+// replace every reference to `params[i]` inside `expr` with `args[i]`
+pub fn substitute(expr: &Expr, params: &[String], args: &[Expr]) -> Expr {
+    match expr {
+        Expr::Ref(name) => match params.iter().position(|param| param == name) {
+            Some(idx) => args[idx].clone(),
+            _ => expr.clone(),
+        },
+        Expr::Comp(op, lhs, rhs) => Expr::Comp(
+            op.clone(),
+            Box::new(substitute(lhs, params, args)),
+            Box::new(substitute(rhs, params, args)),
+        ),
+        Expr::Func(name, fargs) => Expr::Func(
+            name.clone(),
+            fargs
+                .iter()
+                .map(|arg| substitute(arg, params, args))
+                .collect(),
+        ),
+        Expr::Value(Value::Tuple(items)) => Expr::Value(Value::Tuple(
+            items
+                .iter()
+                .map(|item| substitute(item, params, args))
+                .collect(),
+        )),
+        Expr::Value(_) => expr.clone(),
+    }
+}
+
+// true if `var` occurs anywhere inside `expr`
+pub fn depends_on(expr: &Expr, var: &str) -> bool {
+    match expr {
+        Expr::Ref(name) => name == var,
+        Expr::Comp(_, lhs, rhs) => depends_on(lhs, var) || depends_on(rhs, var),
+        Expr::Func(_, args) => args.iter().any(|arg| depends_on(arg, var)),
+        Expr::Value(Value::Tuple(items)) => items.iter().any(|item| depends_on(item, var)),
+        Expr::Value(_) => false,
+    }
+}
+
+fn num(n: i64) -> Expr {
+    Expr::Value(Value::Numeric(NumType::Natural(n)))
+}
+
+fn is_num(expr: &Expr, n: i64) -> bool {
+    *expr == num(n)
+}
+
+fn call(name: &str, arg: Expr) -> Expr {
+    Expr::Func(name.to_string(), vec![arg])
+}
+
+fn comp(op: Operator, lhs: Expr, rhs: Expr) -> Expr {
+    Expr::Comp(op, Box::new(lhs), Box::new(rhs))
+}
+
+fn add(lhs: Expr, rhs: Expr) -> Expr {
+    match (is_num(&lhs, 0), is_num(&rhs, 0)) {
+        (true, _) => rhs,
+        (_, true) => lhs,
+        _ => comp(Operator::Add, lhs, rhs),
+    }
+}
+
+fn sub(lhs: Expr, rhs: Expr) -> Expr {
+    if is_num(&rhs, 0) {
+        lhs
+    } else if is_num(&lhs, 0) {
+        mul(num(-1), rhs)
+    } else {
+        comp(Operator::Sub, lhs, rhs)
+    }
+}
+
+fn mul(lhs: Expr, rhs: Expr) -> Expr {
+    if is_num(&lhs, 0) || is_num(&rhs, 0) {
+        num(0)
+    } else if is_num(&lhs, 1) {
+        rhs
+    } else if is_num(&rhs, 1) {
+        lhs
+    } else {
+        comp(Operator::Mul, lhs, rhs)
+    }
+}
+
+// derivatives divide real numbers but localc divides integers without remainder, so the
+// numerator is made a float
+fn div(lhs: Expr, rhs: Expr) -> Expr {
+    if is_num(&lhs, 0) {
+        num(0)
+    } else if is_num(&rhs, 1) {
+        lhs
+    } else {
+        comp(Operator::Div, real(lhs), rhs)
+    }
+}
+
+fn real(expr: Expr) -> Expr {
+    let one = Expr::Value(Value::Numeric(NumType::Rational(1.)));
+    match expr {
+        Expr::Value(Value::Numeric(NumType::Natural(n))) => {
+            Expr::Value(Value::Numeric(NumType::Rational(n as f64)))
+        }
+        Expr::Value(Value::Numeric(NumType::Rational(_))) => expr,
+        other => comp(Operator::Mul, one, other),
+    }
+}
+
+fn pow(lhs: Expr, rhs: Expr) -> Expr {
+    if is_num(&rhs, 1) {
+        lhs
+    } else {
+        comp(Operator::Pow, lhs, rhs)
+    }
+}
@@ -0,0 +1,124 @@
+use super::*;
+
+use lovm::code::{Code, CodeObject, Instruction};
+
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+// readable listings of code objects. every instruction is printed on its own line together
+// with its resolved operands: constants by value, locals and globals by name and jump
+// targets as labels `L<offset>` that are placed in front of the instruction they point to.
+
+enum Operand {
+    Const,
+    Local,
+    Global,
+    Label,
+    Raw,
+}
+
+// how the operands of an instruction are interpreted
+fn operand_kind(inst: &Instruction) -> Operand {
+    match inst {
+        Instruction::Cpush => Operand::Const,
+        Instruction::Lpush | Instruction::Lpop | Instruction::Lcall => Operand::Local,
+        Instruction::Gpush | Instruction::Gpop | Instruction::Gcall | Instruction::Call => {
+            Operand::Global
+        }
+        Instruction::Jmp | Instruction::Jt | Instruction::Jf => Operand::Label,
+        _ => Operand::Raw,
+    }
+}
+
+// split the code into instructions and their operands. lovm knows how many operands follow
+// every instruction.
+fn instructions(co: &CodeObject) -> Vec<(usize, &Instruction, Vec<usize>)> {
+    let mut insts = vec![];
+    let mut it = co.inner.iter().enumerate();
+    while let Some((offset, code)) = it.next() {
+        match code {
+            Code::Instruction(inst) => {
+                let operands = (0..inst.arguments())
+                    .filter_map(|_| match it.next() {
+                        Some((_, Code::Value(idx))) => Some(*idx),
+                        _ => None,
+                    })
+                    .collect();
+                insts.push((offset, inst, operands));
+            }
+            Code::Value(_) => {}
+        }
+    }
+    insts
+}
+
+pub fn disassemble(co: &CodeObject) -> String {
+    let insts = instructions(co);
+    let labels = insts
+        .iter()
+        .filter(|(_, inst, _)| match operand_kind(inst) {
+            Operand::Label => true,
+            _ => false,
+        })
+        .flat_map(|(_, _, operands)| operands.iter().cloned())
+        .collect::<BTreeSet<_>>();
+
+    let mut out = String::new();
+    if !co.space.locals.is_empty() {
+        writeln!(out, "; locals: {}", join(co.space.locals.iter())).unwrap();
+    }
+
+    for (offset, inst, operands) in insts.iter() {
+        if labels.contains(offset) {
+            writeln!(out, "L{}:", offset).unwrap();
+        }
+        let mnemonic = format!("{:?}", inst).to_lowercase();
+        let operands = operands
+            .iter()
+            .map(|idx| resolve(co, inst, *idx))
+            .collect::<Vec<_>>();
+        if operands.is_empty() {
+            writeln!(out, "    {:04}  {}", offset, mnemonic).unwrap();
+        } else {
+            writeln!(
+                out,
+                "    {:04}  {:<8}{}",
+                offset,
+                mnemonic,
+                operands.join(", ")
+            )
+            .unwrap();
+        }
+    }
+
+    // jumps to the end of the code
+    if labels.contains(&co.inner.len()) {
+        writeln!(out, "L{}:", co.inner.len()).unwrap();
+    }
+
+    out
+}
+
+fn resolve(co: &CodeObject, inst: &Instruction, idx: usize) -> String {
+    let name = |names: &Vec<String>| match names.get(idx) {
+        Some(name) => name.clone(),
+        _ => format!("?{}", idx),
+    };
+    match operand_kind(inst) {
+        Operand::Const => match co.space.consts.get(idx) {
+            Some(value) => format!("{:?}", value),
+            _ => format!("?{}", idx),
+        },
+        Operand::Local => name(&co.space.locals),
+        Operand::Global => name(&co.space.globals),
+        Operand::Label => format!("L{}", idx),
+        Operand::Raw => idx.to_string(),
+    }
+}
+
+fn join<T: std::fmt::Display>(items: impl Iterator<Item = T>) -> String {
+    items
+        .map(|item| item.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
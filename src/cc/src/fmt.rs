@@ -0,0 +1,123 @@
+use localc_cc_lib::formatter::format_source;
+
+use std::env;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::process;
+
+const USAGE: &str = "\
+usage: localc-fmt [options] [file.lc ...]
+
+formats the given files in place. reads from stdin and writes to stdout if no files are given.
+
+options:
+    --check             do not write anything, fail if a file is not formatted
+    -h, --help          print this message
+    -V, --version       print the version";
+
+struct Config {
+    check: bool,
+    files: Vec<String>,
+}
+
+enum Action {
+    Format(Config),
+    Help,
+    Version,
+}
+
+fn get_config_from_args<I>(args: I) -> Result<Action, String>
+where
+    I: Iterator<Item = String>,
+{
+    let mut config = Config {
+        check: false,
+        files: vec![],
+    };
+
+    for arg in args {
+        match arg.as_ref() {
+            "--check" => config.check = true,
+            "-h" | "--help" => return Ok(Action::Help),
+            "-V" | "--version" => return Ok(Action::Version),
+            flag if flag.starts_with('-') => return Err(format!("unknown option `{}`", flag)),
+            _ => config.files.push(arg.clone()),
+        }
+    }
+
+    Ok(Action::Format(config))
+}
+
+fn read(mut input: impl Read, name: &str) -> Result<String, String> {
+    let mut src = String::new();
+    input
+        .read_to_string(&mut src)
+        .map_err(|err| format!("could not read `{}`: {}", name, err))?;
+    Ok(src)
+}
+
+// returns true if the file was already formatted
+fn format_file(path: &str, check: bool) -> Result<bool, String> {
+    let file = File::open(path).map_err(|err| format!("could not open `{}`: {}", path, err))?;
+    let src = read(file, path)?;
+    let formatted = format_source(&src).map_err(|err| format!("{}:{}", path, err))?;
+
+    if formatted == src {
+        return Ok(true);
+    }
+    if check {
+        println!("{} is not formatted", path);
+    } else {
+        File::create(path)
+            .and_then(|mut file| file.write_all(formatted.as_bytes()))
+            .map_err(|err| format!("could not write `{}`: {}", path, err))?;
+    }
+    Ok(false)
+}
+
+fn run(config: Config) -> Result<bool, String> {
+    if config.files.is_empty() {
+        let src = read(std::io::stdin(), "stdin")?;
+        let formatted = format_source(&src).map_err(|err| format!("stdin:{}", err))?;
+        if !config.check {
+            print!("{}", formatted);
+        }
+        return Ok(formatted == src);
+    }
+
+    let mut formatted = true;
+    for path in config.files.iter() {
+        formatted &= format_file(path, config.check)?;
+    }
+    Ok(formatted)
+}
+
+pub fn main() {
+    let config = match get_config_from_args(env::args().skip(1)) {
+        Ok(Action::Format(config)) => config,
+        Ok(Action::Help) => {
+            println!("{}", USAGE);
+            return;
+        }
+        Ok(Action::Version) => {
+            println!("localc-fmt {}", env!("CARGO_PKG_VERSION"));
+            return;
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            eprintln!("try `localc-fmt --help`");
+            process::exit(2);
+        }
+    };
+
+    let check = config.check;
+    match run(config) {
+        // unformatted input only fails in check mode
+        Ok(formatted) if formatted || !check => {}
+        Ok(_) => process::exit(1),
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1);
+        }
+    }
+}
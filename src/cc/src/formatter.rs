@@ -0,0 +1,229 @@
+use super::*;
+
+// source formatting. every statement is parsed and printed again, which normalizes spacing
+// and parentheses. consecutive overloads of the same function are aligned at their `=`.
+// comments start with `#` and run until the end of the line; they are kept as they are.
+
+// split `line` into code and comment. a `#` inside a string does not start a comment.
+pub fn split_comment(line: &str) -> (&str, Option<&str>) {
+    let mut in_str = false;
+    let mut prev = ' ';
+    for (idx, c) in line.char_indices() {
+        match c {
+            // a quote directly after an identifier is part of its name, e.g. `f'`
+            '\'' if in_str || !is_ident_char(prev) => in_str = !in_str,
+            '#' if !in_str => return (&line[..idx], Some(&line[idx..])),
+            _ => {}
+        }
+        prev = c;
+    }
+    (line, None)
+}
+
+pub fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '\''
+}
+
+// true if `code` opens more brackets than it closes. brackets in strings are ignored.
+pub fn is_incomplete(code: &str) -> bool {
+    let mut depth = 0;
+    let mut in_str = false;
+    let mut prev = ' ';
+    for c in code.chars() {
+        match c {
+            '\'' if in_str || !is_ident_char(prev) => in_str = !in_str,
+            '(' | '{' | '[' if !in_str => depth += 1,
+            ')' | '}' | ']' if !in_str => depth -= 1,
+            _ => {}
+        }
+        prev = c;
+    }
+    0 < depth
+}
+
+// code of every statement in `src` with the number of its first line. comments are removed
+// and the lines of statements spanning multiple lines are joined.
+pub fn statements(src: &str) -> Vec<(usize, String)> {
+    let mut statements = vec![];
+    let mut pending: Option<(usize, String)> = None;
+
+    for (i, line) in src.lines().enumerate() {
+        let (code, _) = split_comment(line);
+        let (start, mut stmt) = match pending.take() {
+            Some((start, mut stmt)) => {
+                stmt.push(' ');
+                (start, stmt)
+            }
+            _ if code.trim().is_empty() => continue,
+            _ => (i + 1, String::new()),
+        };
+        stmt.push_str(code);
+        if is_incomplete(&stmt) {
+            pending = Some((start, stmt));
+        } else {
+            statements.push((start, stmt));
+        }
+    }
+
+    // unbalanced brackets at the end are reported by the parser
+    statements.extend(pending);
+    statements
+}
+
+enum Line {
+    Blank,
+    Comment(String),
+    Statement {
+        // left hand side of an overload definition and the function name
+        overload: Option<(String, String)>,
+        code: String,
+        comment: Option<String>,
+    },
+}
+
+// format a whole source file. fails with `line: message` on the first statement that does
+// not parse.
+pub fn format_source(src: &str) -> Result<String, String> {
+    let parser = expr::ExprParser::new();
+    let mut lines = vec![];
+    let mut pending = String::new();
+    let mut pending_start = 0;
+    let mut comments = vec![];
+
+    for (i, line) in src.lines().enumerate() {
+        let (code, comment) = split_comment(line);
+        let code = code.trim();
+
+        if pending.is_empty() {
+            if code.is_empty() {
+                lines.push(match comment {
+                    Some(comment) => Line::Comment(comment.trim_end().to_string()),
+                    _ => Line::Blank,
+                });
+                continue;
+            }
+            pending_start = i + 1;
+        } else {
+            pending.push(' ');
+        }
+        pending.push_str(code);
+        comments.extend(comment.map(|c| c.trim_end().to_string()));
+
+        if is_incomplete(&pending) {
+            continue;
+        }
+
+        let ast = parser
+            .parse(&pending)
+            .map_err(|err| format!("{}: {:?}", pending_start, err))?;
+        // comments inside of multiline statements are moved in front of it
+        let comment = comments.pop();
+        lines.extend(comments.drain(..).map(Line::Comment));
+        lines.push(statement(&ast, comment));
+        pending.clear();
+    }
+
+    if !pending.is_empty() {
+        return Err(format!("{}: unbalanced brackets", pending_start));
+    }
+
+    Ok(render(lines))
+}
+
+fn statement(ast: &Expr, comment: Option<String>) -> Line {
+    if let Expr::Comp(Operator::Store, lhs, rhs) = ast {
+        if let Expr::Func(name, _) = lhs.as_ref() {
+            return Line::Statement {
+                overload: Some((lhs.to_string(), name.clone())),
+                code: rhs.to_string(),
+                comment,
+            };
+        }
+    }
+    Line::Statement {
+        overload: None,
+        code: ast.to_string(),
+        comment,
+    }
+}
+
+fn render(lines: Vec<Line>) -> String {
+    let mut out = String::new();
+    let mut i = 0;
+    let mut blank = true;
+
+    while i < lines.len() {
+        match &lines[i] {
+            // no blank lines at the start and no consecutive ones
+            Line::Blank => {
+                if !blank {
+                    out.push('\n');
+                }
+                blank = true;
+                i += 1;
+                continue;
+            }
+            Line::Comment(comment) => {
+                out.push_str(comment);
+                out.push('\n');
+            }
+            Line::Statement {
+                overload: Some((_, name)),
+                ..
+            } => {
+                // all directly following overloads of the same function
+                let group = lines[i..]
+                    .iter()
+                    .take_while(|line| match line {
+                        Line::Statement {
+                            overload: Some((_, other)),
+                            ..
+                        } => other == name,
+                        _ => false,
+                    })
+                    .collect::<Vec<_>>();
+                let width = group
+                    .iter()
+                    .map(|line| match line {
+                        Line::Statement {
+                            overload: Some((lhs, _)),
+                            ..
+                        } => lhs.chars().count(),
+                        _ => 0,
+                    })
+                    .max()
+                    .unwrap_or(0);
+                for line in group.iter() {
+                    if let Line::Statement {
+                        overload: Some((lhs, _)),
+                        code,
+                        comment,
+                    } = line
+                    {
+                        let lhs = format!("{:width$}", lhs, width = width);
+                        push_statement(&mut out, &format!("{} = {}", lhs, code), comment);
+                    }
+                }
+                i += group.len() - 1;
+            }
+            Line::Statement { code, comment, .. } => push_statement(&mut out, code, comment),
+        }
+        blank = false;
+        i += 1;
+    }
+
+    // no blank lines at the end
+    while out.ends_with("\n\n") {
+        out.pop();
+    }
+    out
+}
+
+fn push_statement(out: &mut String, code: &str, comment: &Option<String>) {
+    out.push_str(code);
+    if let Some(comment) = comment {
+        out.push(' ');
+        out.push_str(comment);
+    }
+    out.push('\n');
+}
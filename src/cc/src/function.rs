@@ -0,0 +1,383 @@
+use super::*;
+
+use lovm::gen::*;
+
+use std::iter::Peekable;
+use std::slice::Iter;
+
+// interrupt raised for calling natives
+pub const NATIVE_INTERRUPT: usize = 10;
+
+// minimum number of distinct constants for generating a jump table
+pub const JUMP_TABLE_THRESHOLD: usize = 4;
+
+// jump tables need a native that pushes `true` if its argument is a number, because `cmp_lt`
+// is only defined for numbers
+#[derive(Clone, Copy, Debug)]
+pub struct JumpTables {
+    pub threshold: usize,
+    pub is_number: usize,
+}
+
+// localc's intermediate representation of a function. every function can be called with
+// arguments that are variant over length and items.
+
+#[derive(Debug)]
+pub struct Function {
+    overloads: Vec<(Overload, CodeBuilder)>,
+    // source expressions of overloads that were defined in localc
+    definitions: Vec<(Overload, Expr)>,
+    memo: Option<Memo>,
+    // id of a native that takes any number of arguments no overload accepts
+    variadic: Option<usize>,
+}
+
+// ids of the natives used for caching results of a memoized function. `lookup` tests if the
+// arguments are cached, `fetch` pushes the cached result and `store` caches the result that
+// is on top of the stack.
+#[derive(Clone, Copy, Debug)]
+pub struct Memo {
+    pub lookup: usize,
+    pub fetch: usize,
+    pub store: usize,
+}
+
+// one line per overload: the parameters followed by the source expression. natives have no
+// source and are marked as such.
+impl std::fmt::Display for Function {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        for (overload, _) in self.overloads.iter() {
+            match self.definitions.iter().find(|(other, _)| other == overload) {
+                Some((_, expr)) => writeln!(f, "{} = {}", overload, expr)?,
+                _ => writeln!(f, "{} = <native>", overload)?,
+            }
+        }
+        if self.variadic.is_some() {
+            writeln!(f, "(...) = <native>")?;
+        }
+        Ok(())
+    }
+}
+
+impl Function {
+    pub fn new() -> Self {
+        Self {
+            overloads: vec![],
+            definitions: vec![],
+            memo: None,
+            variadic: None,
+        }
+    }
+
+    pub fn overload<T>(&mut self, overload: T, fb: CodeBuilder)
+    where
+        T: Into<Overload>,
+    {
+        let overload = overload.into();
+        self.overloads
+            .retain(|(other, _)| other == &overload || !other.same_pattern(&overload));
+        match self
+            .overloads
+            .binary_search_by_key(&&overload, |item| &item.0)
+        {
+            Ok(idx) => self.overloads.get_mut(idx).unwrap().1 = fb,
+            Err(idx) => self.overloads.insert(idx, (overload, fb)),
+        }
+    }
+
+    // remember the localc expression an overload was compiled from
+    pub fn define<T>(&mut self, overload: T, expr: Expr)
+    where
+        T: Into<Overload>,
+    {
+        let overload = overload.into();
+        self.definitions
+            .retain(|(other, _)| other == &overload || !other.same_pattern(&overload));
+        match self
+            .definitions
+            .binary_search_by_key(&&overload, |item| &item.0)
+        {
+            Ok(idx) => self.definitions.get_mut(idx).unwrap().1 = expr,
+            Err(idx) => self.definitions.insert(idx, (overload, expr)),
+        }
+    }
+
+    pub fn overloads(&self) -> impl Iterator<Item = &Overload> {
+        self.overloads.iter().map(|(overload, _)| overload)
+    }
+
+    pub fn definitions(&self) -> impl Iterator<Item = &(Overload, Expr)> {
+        self.definitions.iter()
+    }
+
+    pub fn memoize(&mut self, memo: Memo) {
+        self.memo = Some(memo);
+    }
+
+    pub fn is_memoized(&self) -> bool {
+        self.memo.is_some()
+    }
+
+    pub fn variadic(&mut self, id: usize) {
+        self.variadic = Some(id);
+    }
+
+    // generate a lovm-executable representation of the current function. overloads are
+    // dispatched through linear chains.
+    pub fn build(&self) -> Result<CodeObject, ()> {
+        self.build_with(None, None)
+    }
+
+    // like `build`, but argument tables dispatch through a jump table if `tables` is set and
+    // there are at least `threshold` distinct constants for the first argument. the native
+    // `interrupt` is called before every dispatch and stops the execution by failing.
+    pub fn build_with(
+        &self,
+        tables: Option<JumpTables>,
+        interrupt: Option<usize>,
+    ) -> Result<CodeObject, ()> {
+        // every localc function takes an obligatory parameter for specifying the argument
+        // amount that was meant to be passed
+        let mut atable = CodeBuilder::new().with_params(vec!["argc"]);
+        let mut it = self.overloads.iter().peekable();
+
+        if let Some(interrupt) = interrupt {
+            call_native(&mut atable, interrupt, &[], 0);
+            let mut stop = CodeBuilder::new();
+            stop.step(gen::Operation::ret());
+            atable.branch_if(stop);
+        }
+
+        // the dispatch runs inside a loop. overloads return from the function as usual, but
+        // self tail calls push their arguments and continue with the next iteration, which
+        // pops `argc` again and dispatches without growing the call stack.
+
+        while it.peek().is_some() {
+            // build an atable (argument table) that contains overloads with the same argument
+            // count `argc`. advances `it` until the argument count is different.
+            let (argc, vtable) = build_vtable(&mut it, self.memo, self.variadic, tables);
+
+            // check the passed argument count against the tables
+            // expected count. branches to atable if equal
+            atable.step(gen::Operation::cmp_eq().var("argc").op(argc).end());
+            atable.branch_if(vtable);
+        }
+
+        // the arguments of other counts are still on the stack
+        if let Some(variadic) = self.variadic {
+            call_variadic(&mut atable, variadic);
+        }
+        atable.step(gen::Operation::ret());
+
+        let mut entry = CodeBuilder::new();
+        entry.repeat(atable);
+
+        entry.build(true)
+    }
+}
+
+// natives are called by pushing the arguments, the argument count and the natives id before
+// raising `NATIVE_INTERRUPT`. `on_stack` values pushed by the caller are passed in front of `args`.
+pub fn call_native(block: &mut CodeBuilder, id: usize, args: &[String], on_stack: usize) {
+    for arg in args.iter() {
+        block.step(gen::Operation::push().var(arg.clone()).end());
+    }
+    block.step(gen::Operation::push().op(on_stack + args.len()).end());
+    block.step(gen::Operation::push().op(id).end());
+    block.step(gen::Operation::int().op(NATIVE_INTERRUPT).end());
+}
+
+// like `call_native` but passes the `argc` values the caller of a function pushed
+pub fn call_variadic(block: &mut CodeBuilder, id: usize) {
+    block.step(gen::Operation::push().var("argc").end());
+    block.step(gen::Operation::push().op(id).end());
+    block.step(gen::Operation::int().op(NATIVE_INTERRUPT).end());
+}
+
+// building the vtable works as follows:
+// - take first argument (which is guaranteed to be Some(_) by caller) and extract argument count
+// - compile dispatch entry using overload; merge first block onto `cases` (because all other
+// checks will live in own blocks)
+// - for all items in `it` having the same argc:
+//      - compile dispatch entry using overload into function
+//
+//  add_case will work recursively by peeking if the next argument has the same argc. if so, it
+//  takes that argument and immediately turns it into a function by calling add_case again. this
+//  way all cases will be nested in another allowing for correct `jf` branching.
+
+// if the atable jumps to such a block, we need to pop the desired argument count from the stack
+fn build_vtable(
+    it: &mut Peekable<Iter<(Overload, CodeBuilder)>>,
+    memo: Option<Memo>,
+    variadic: Option<usize>,
+    tables: Option<JumpTables>,
+) -> (usize, CodeBuilder) {
+    let first = it.next().unwrap();
+    let argc = first.0.count();
+    // generate default arguments in form `arg0, arg1, ... argn`
+    let params = (0..argc).map(|i| format!("arg{}", i)).collect::<Vec<_>>();
+    // default arguments are popped off the stack here
+    let mut cases = CodeBuilder::new().with_params(params.clone());
+
+    // memoized functions return the cached result before dispatching
+    if let Some(memo) = memo {
+        call_native(&mut cases, memo.lookup, &params, 0);
+        let mut hit = CodeBuilder::new();
+        call_native(&mut hit, memo.fetch, &params, 0);
+        hit.step(Operation::ret());
+        cases.branch_if(hit);
+    }
+
+    let mut entries = vec![first];
+    while it.peek().as_ref().map_or(false, |(o, _)| argc == o.count()) {
+        entries.push(it.next().unwrap());
+    }
+
+    // overloads are sorted, so all overloads with a numeric constant as first argument come
+    // first. they are dispatched by a binary search over the constant if there are enough.
+    // `cmp_lt` is only defined for numbers, so other arguments skip the search and fall
+    // through to the remaining overloads. integers and floats are not mixed in one table
+    // because an argument equal to the pivot could end up in the wrong half.
+    let groups = group_by_first_constant(&entries);
+    let grouped = groups.iter().map(|(_, group)| group.len()).sum::<usize>();
+    let uniform = groups
+        .windows(2)
+        .all(|pair| is_natural(pair[0].0) == is_natural(pair[1].0));
+    let rest = match tables {
+        Some(tables) if tables.threshold <= groups.len() && uniform => {
+            call_native(&mut cases, tables.is_number, &params[..1], 0);
+            cases.branch_if(build_jump_table(&groups, &params, memo));
+            &entries[grouped..]
+        }
+        _ => &entries[..],
+    };
+
+    // if the condition turned out to false, continue with next block
+    for entry in rest.iter() {
+        cases.step(create_case(entry, &params, memo));
+    }
+
+    // no overload matched the arguments that were already popped
+    if let Some(variadic) = variadic {
+        call_native(&mut cases, variadic, &params, 0);
+        cases.step(Operation::ret());
+    }
+
+    (argc, cases)
+}
+
+// overloads with the same numeric constant in first position
+type Group<'a> = (&'a ast::Value, Vec<&'a (Overload, CodeBuilder)>);
+
+fn group_by_first_constant<'a>(entries: &[&'a (Overload, CodeBuilder)]) -> Vec<Group<'a>> {
+    let mut groups: Vec<Group<'a>> = vec![];
+    for &entry in entries.iter() {
+        let value = match entry.0.iter().next() {
+            Some(Expr::Value(value @ ast::Value::Numeric(_))) => value,
+            _ => break,
+        };
+        match groups.last_mut() {
+            Some((last, group)) if *last == value => group.push(entry),
+            _ => groups.push((value, vec![entry])),
+        }
+    }
+    groups
+}
+
+fn is_natural(value: &ast::Value) -> bool {
+    match value {
+        ast::Value::Numeric(NumType::Natural(_)) => true,
+        _ => false,
+    }
+}
+
+// binary search over sorted constants. every level compares `arg0` against the middle
+// constant and branches into the lower half if it is smaller. leaves are linear chains.
+fn build_jump_table(groups: &[Group], params: &[String], memo: Option<Memo>) -> CodeBuilder {
+    let mut table = CodeBuilder::new();
+
+    if groups.len() <= 2 {
+        for (_, group) in groups.iter() {
+            for entry in group.iter() {
+                table.step(create_case(entry, params, memo));
+            }
+        }
+        return table;
+    }
+
+    let (lower, upper) = groups.split_at(groups.len() / 2);
+    let pivot = Expr::Value(upper[0].0.clone());
+
+    table.step(gen::Operation::cmp_lt().var("arg0").op(pivot).end());
+    table.branch_if(build_jump_table(lower, params, memo));
+    table.step(build_jump_table(upper, params, memo));
+
+    table
+}
+
+fn create_case(
+    (overload, fb): &(Overload, CodeBuilder),
+    params: &[String],
+    memo: Option<Memo>,
+) -> CodeBuilder {
+    let mut case = CodeBuilder::new();
+
+    // not all values can be compared (e.g. variable idents), so
+    // how many components really add up to the final condition?
+    let mut comps = 0;
+    for (i, param) in overload.iter().enumerate() {
+        match param {
+            // variable name in function declaration matches everything
+            Expr::Ref(_) => {}
+            Expr::Value(_) => {
+                let name = format!("arg{}", i);
+                case.step(gen::Operation::cmp_eq().var(name).op(param.clone()).end());
+                if 0 < comps {
+                    case.step(gen::Operation::and());
+                }
+                comps += 1;
+            }
+            // no other variants allowed in overload
+            _ => unreachable!(),
+        }
+    }
+
+    // TODO: this push could be avoided somehow
+    // in `match-everything` conditions we have to push a default value
+    if comps == 0 {
+        case.step(Operation::push().op(true).end());
+    }
+
+    let yes_branch = {
+        let args = overload
+            .iter()
+            .enumerate()
+            .filter_map(|(i, param)| match param {
+                Expr::Ref(_) => Some(format!("arg{}", i)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        // this branch will be executed if the condition generated above is true
+        let mut yes_branch = CodeBuilder::new();
+
+        // add prelude for passing function arguments
+        // calling order does not need `.rev()`
+        for arg in args.into_iter() {
+            yes_branch.step(gen::Operation::push().var(arg).end());
+        }
+
+        yes_branch.step(fb.clone());
+        // the result is on top of the stack and passed to `store` in front of the arguments
+        if let Some(memo) = memo {
+            call_native(&mut yes_branch, memo.store, params, 1);
+        }
+        yes_branch.step(Operation::ret());
+
+        yes_branch
+    };
+
+    case.branch_if(yes_branch);
+
+    case
+}
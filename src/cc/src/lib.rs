@@ -1,13 +1,42 @@
 #[macro_use]
 extern crate lalrpop_util;
+#[macro_use]
+extern crate log;
 
 lalrpop_mod!(pub expr);
 
 pub mod ast;
 pub mod compiler;
+pub mod diff;
+pub mod disasm;
+pub mod formatter;
+pub mod function;
+pub mod optimize;
+pub mod overload;
+pub mod simplify;
 
 use ast::*;
 use compiler::*;
 use expr::*;
+use function::*;
+use optimize::*;
+use overload::*;
 
 use lovm::*;
+
+// byte offset in the source at which parsing failed. `None` if the input ended unexpectedly.
+pub fn error_offset<T, E>(err: &lalrpop_util::ParseError<usize, T, E>) -> Option<usize> {
+    use lalrpop_util::ParseError::*;
+
+    match err {
+        InvalidToken { location } => Some(*location),
+        UnrecognizedToken {
+            token: Some((start, _, _)),
+            ..
+        } => Some(*start),
+        ExtraToken {
+            token: (start, _, _),
+        } => Some(*start),
+        _ => None,
+    }
+}
@@ -0,0 +1,208 @@
+use super::*;
+
+use crate::ast::Value;
+
+use std::collections::HashMap;
+
+// compile-time evaluation of subtrees that only consist of literals, immutable constants and
+// calls to pure functions. integer operands keep integer semantics: `7 / 2` folds to `3`.
+// operations that would fail at runtime (division by zero, overflow) are left untouched.
+
+pub type PureFn = fn(&[NumType]) -> Option<NumType>;
+
+#[derive(Clone, Default)]
+pub struct FoldContext {
+    constants: HashMap<String, Value>,
+    functions: HashMap<String, Vec<(usize, PureFn)>>,
+    // functions taking any number of arguments
+    variadic: HashMap<String, PureFn>,
+}
+
+impl FoldContext {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn constant(&mut self, name: &str, value: Value) {
+        self.constants.insert(name.to_string(), value);
+    }
+
+    pub fn is_constant(&self, name: &str) -> bool {
+        self.constants.contains_key(name)
+    }
+
+    // `name` called with `arity` numeric arguments can be evaluated using `func`
+    pub fn function(&mut self, name: &str, arity: usize, func: PureFn) {
+        let overloads = self
+            .functions
+            .entry(name.to_string())
+            .or_insert_with(Vec::new);
+        overloads.retain(|(argc, _)| *argc != arity);
+        overloads.push((arity, func));
+    }
+
+    // `name` called with numeric arguments of any count can be evaluated using `func`
+    pub fn variadic(&mut self, name: &str, func: PureFn) {
+        self.variadic.insert(name.to_string(), func);
+    }
+
+    // true if `name` is a pure function. pure functions always return numbers.
+    pub fn is_pure(&self, name: &str) -> bool {
+        self.functions.contains_key(name) || self.variadic.contains_key(name)
+    }
+
+    // a redefined function is no longer known to be pure. constants of the same name are kept
+    // because functions and globals live in separate namespaces.
+    pub fn forget_function(&mut self, name: &str) {
+        self.functions.remove(name);
+        self.variadic.remove(name);
+    }
+
+    // `name` was removed as both function and global
+    pub fn forget(&mut self, name: &str) {
+        self.constants.remove(name);
+        self.forget_function(name);
+    }
+}
+
+pub fn fold(expr: &Expr, ctx: &FoldContext) -> Expr {
+    fold_with_params(expr, ctx, &vec![])
+}
+
+// like `fold` but the references in `params` shadow constants of the same name
+pub fn fold_with_params(expr: &Expr, ctx: &FoldContext, params: &TupleType) -> Expr {
+    match expr {
+        Expr::Ref(name) if !params.contains(expr) => match ctx.constants.get(name) {
+            Some(value) => Expr::Value(value.clone()),
+            _ => expr.clone(),
+        },
+        Expr::Comp(Operator::Store, _, _) => expr.clone(),
+        Expr::Comp(op, lhs, rhs) => {
+            let lhs = fold_with_params(lhs, ctx, params);
+            let rhs = fold_with_params(rhs, ctx, params);
+            match (&lhs, &rhs) {
+                (Expr::Value(a), Expr::Value(b)) => match eval(op, a, b) {
+                    Some(value) => Expr::Value(value),
+                    _ => Expr::Comp(op.clone(), Box::new(lhs), Box::new(rhs)),
+                },
+                _ => Expr::Comp(op.clone(), Box::new(lhs), Box::new(rhs)),
+            }
+        }
+        Expr::Func(name, args) => {
+            let args = args
+                .iter()
+                .map(|arg| fold_with_params(arg, ctx, params))
+                .collect::<Vec<_>>();
+            match call(ctx, name, &args) {
+                Some(n) => Expr::Value(Value::Numeric(n)),
+                _ => Expr::Func(name.clone(), args),
+            }
+        }
+        Expr::Value(Value::Tuple(items)) => Expr::Value(Value::Tuple(
+            items
+                .iter()
+                .map(|item| fold_with_params(item, ctx, params))
+                .collect(),
+        )),
+        Expr::Value(Value::Set(items)) => Expr::Value(Value::Set(
+            items
+                .iter()
+                .map(|(key, value)| {
+                    (
+                        key.as_ref().map(|key| fold_with_params(key, ctx, params)),
+                        fold_with_params(value, ctx, params),
+                    )
+                })
+                .collect(),
+        )),
+        other => other.clone(),
+    }
+}
+
+fn call(ctx: &FoldContext, name: &str, args: &[Expr]) -> Option<NumType> {
+    let func = ctx
+        .functions
+        .get(name)
+        .and_then(|overloads| overloads.iter().find(|(arity, _)| *arity == args.len()))
+        .map(|(_, func)| func)
+        .or_else(|| ctx.variadic.get(name))?;
+    let args = args
+        .iter()
+        .map(|arg| match arg {
+            Expr::Value(Value::Numeric(n)) => Some(n.clone()),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+    func(&args)
+}
+
+// evaluate `a op b` or return `None` if the result is not known at compile time
+pub fn eval(op: &Operator, a: &Value, b: &Value) -> Option<Value> {
+    use self::NumType::*;
+
+    match (a, b) {
+        (Value::Numeric(Natural(a)), Value::Numeric(Natural(b))) => {
+            let (a, b) = (*a, *b);
+            let n = match op {
+                Operator::Add => a.checked_add(b).map(Natural),
+                Operator::Sub => a.checked_sub(b).map(Natural),
+                Operator::Mul => a.checked_mul(b).map(Natural),
+                Operator::Div if b != 0 => a.checked_div(b).map(Natural),
+                Operator::Rem if b != 0 => a.checked_rem(b).map(Natural),
+                Operator::Pow if 0 <= b && b <= u32::max_value() as i64 => {
+                    a.checked_pow(b as u32).map(Natural)
+                }
+                _ => None,
+            };
+            n.map(Value::Numeric)
+                .or_else(|| compare(op, a.partial_cmp(&b)?))
+        }
+        (Value::Numeric(a), Value::Numeric(b)) => {
+            let (a, b) = (float(a), float(b));
+            let n = match op {
+                Operator::Add => Some(a + b),
+                Operator::Sub => Some(a - b),
+                Operator::Mul => Some(a * b),
+                Operator::Div if b != 0. => Some(a / b),
+                Operator::Rem if b != 0. => Some(a % b),
+                Operator::Pow => Some(a.powf(b)),
+                _ => None,
+            };
+            match n {
+                Some(n) if n.is_finite() => Some(Value::Numeric(Rational(n))),
+                Some(_) => None,
+                _ => compare(op, a.partial_cmp(&b)?),
+            }
+        }
+        (Value::Logical(a), Value::Logical(b)) => match op {
+            Operator::And => Some(Value::Logical(*a && *b)),
+            Operator::Or => Some(Value::Logical(*a || *b)),
+            Operator::Eq => Some(Value::Logical(a == b)),
+            Operator::Ne => Some(Value::Logical(a != b)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn compare(op: &Operator, ordering: std::cmp::Ordering) -> Option<Value> {
+    use std::cmp::Ordering::*;
+
+    let result = match op {
+        Operator::Eq => ordering == Equal,
+        Operator::Ne => ordering != Equal,
+        Operator::Ge => ordering != Less,
+        Operator::Gt => ordering == Greater,
+        Operator::Le => ordering != Greater,
+        Operator::Lt => ordering == Less,
+        _ => return None,
+    };
+    Some(Value::Logical(result))
+}
+
+fn float(n: &NumType) -> f64 {
+    match n {
+        NumType::Natural(n) => *n as f64,
+        NumType::Rational(n) => *n,
+    }
+}
@@ -0,0 +1,164 @@
+use super::*;
+
+use std::cmp::Ordering;
+
+// `Overload` is a function signature where `Expr` is either a constant or an identifier.
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Overload(Vec<Expr>);
+
+impl Overload {
+    pub fn new() -> Self {
+        Self(vec![])
+    }
+
+    pub fn count(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Expr> {
+        self.0.iter()
+    }
+
+    // returns true if the overload matches all arguments
+    pub fn accepts_count(&self, count: usize) -> bool {
+        if self.count() != count {
+            return false;
+        }
+        for arg in self.iter() {
+            match arg {
+                Expr::Ref(_) => {}
+                _ => return false,
+            }
+        }
+        true
+    }
+
+    // true if both overloads match the same arguments, i.e. they only differ in the names of
+    // their variables. the one defined later shadows the other.
+    pub fn same_pattern(&self, other: &Self) -> bool {
+        self.count() == other.count()
+            && self.iter().zip(other.iter()).all(|pair| match pair {
+                (Expr::Ref(_), Expr::Ref(_)) => true,
+                (s, o) => s == o,
+            })
+    }
+
+    // tests if the arguments supplied satisfy the `Overload`s constraints
+    pub fn accepts(&self, args: Vec<Expr>) -> bool {
+        // equal if both are empty
+        if self.0.is_empty() && args.is_empty() {
+            return true;
+        }
+        // size must be equal
+        if self.count() != args.len() {
+            return false;
+        }
+
+        for (p, a) in self.0.iter().zip(args.iter()) {
+            match p {
+                Expr::Value(_) if p == a => {}
+                Expr::Ref(_) => {}
+                _ => return false,
+            }
+        }
+
+        true
+    }
+}
+
+impl<T> From<Vec<T>> for Overload
+where
+    T: Into<Expr>,
+{
+    fn from(from: Vec<T>) -> Self {
+        let exprs = from.into_iter().map(|f| f.into()).collect::<Vec<_>>();
+        Overload(exprs)
+    }
+}
+
+impl std::fmt::Display for Overload {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        write!(f, "(")?;
+        for (i, param) in self.iter().enumerate() {
+            if 0 < i {
+                write!(f, ", ")?;
+            }
+            match param {
+                Expr::Ref(name) => write!(f, "{}", name)?,
+                Expr::Value(value) => write!(f, "{}", value)?,
+                _ => unreachable!(),
+            }
+        }
+        write!(f, ")")
+    }
+}
+
+impl std::cmp::Eq for Overload {}
+
+impl std::cmp::Ord for Overload {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.count(), other.count()) {
+            (s, o) if s == 0 && o == 0 => Ordering::Equal,
+            (s, o) if s < o => Ordering::Less,
+            (s, o) if s > o => Ordering::Greater,
+            _ => {
+                for (s, o) in self.0.iter().zip(other.iter()) {
+                    let result = match (s, o) {
+                        (Expr::Value(s), Expr::Value(o)) => cmp_constants(s, o),
+                        (Expr::Ref(s), Expr::Ref(o)) => s.cmp(&o),
+                        (Expr::Value(_), _) => Ordering::Less,
+                        (Expr::Ref(_), _) => Ordering::Greater,
+                        // only `Value` and `Ref` are allowed in overload
+                        _ => unreachable!(),
+                    };
+                    if result != Ordering::Equal {
+                        return result;
+                    }
+                }
+                Ordering::Equal
+            }
+        }
+    }
+}
+
+impl std::cmp::PartialOrd for Overload {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// total order of constants: numbers come first and are ordered by value with an integer before
+// an equal float. other constants are ordered by kind and then by their source.
+fn cmp_constants(a: &ast::Value, b: &ast::Value) -> Ordering {
+    fn rank(value: &ast::Value) -> usize {
+        match value {
+            ast::Value::Numeric(_) => 0,
+            ast::Value::Logical(_) => 1,
+            ast::Value::Str(_) => 2,
+            ast::Value::Nil => 3,
+            _ => 4,
+        }
+    }
+    fn float(n: &NumType) -> f64 {
+        match n {
+            NumType::Natural(n) => *n as f64,
+            NumType::Rational(n) => *n,
+        }
+    }
+
+    match (a, b) {
+        (ast::Value::Numeric(x), ast::Value::Numeric(y)) => {
+            float(x).total_cmp(&float(y)).then_with(|| match (x, y) {
+                (NumType::Natural(x), NumType::Natural(y)) => x.cmp(y),
+                (NumType::Natural(_), NumType::Rational(_)) => Ordering::Less,
+                (NumType::Rational(_), NumType::Natural(_)) => Ordering::Greater,
+                _ => Ordering::Equal,
+            })
+        }
+        (ast::Value::Logical(x), ast::Value::Logical(y)) => x.cmp(y),
+        _ => rank(a)
+            .cmp(&rank(b))
+            .then_with(|| a.to_string().cmp(&b.to_string())),
+    }
+}
@@ -0,0 +1,426 @@
+use super::*;
+
+use crate::ast::Value;
+
+use std::convert::TryFrom;
+
+// rewrite-based simplification of arithmetic expressions. sums and products are brought into a
+// normal form `c1 * f1 ^ e1 * ... + c2 * ...` where like terms and equal factors are combined.
+// the rewrite assumes real arithmetic, e.g. `x * 2 / 2` becomes `x` even if `x` is an integer.
+// fractions are written as quotients of integers, so they divide like they did in the input.
+// powers are only merged for integer exponents because `(x ^ 2) ^ 0.5` is not `x` for
+// negative `x`.
+
+pub fn simplify(expr: &Expr) -> Expr {
+    match expr {
+        Expr::Comp(Operator::Add, _, _)
+        | Expr::Comp(Operator::Sub, _, _)
+        | Expr::Comp(Operator::Mul, _, _)
+        | Expr::Comp(Operator::Div, _, _)
+        | Expr::Comp(Operator::Pow, _, _)
+        | Expr::Value(Value::Numeric(_)) => Sum::from_expr(expr).into_expr(),
+        Expr::Comp(op, lhs, rhs) => {
+            Expr::Comp(op.clone(), Box::new(simplify(lhs)), Box::new(simplify(rhs)))
+        }
+        Expr::Func(name, args) => Expr::Func(name.clone(), args.iter().map(simplify).collect()),
+        Expr::Value(Value::Tuple(items)) => {
+            Expr::Value(Value::Tuple(items.iter().map(simplify).collect()))
+        }
+        Expr::Value(Value::Set(items)) => Expr::Value(Value::Set(
+            items
+                .iter()
+                .map(|(key, value)| (key.as_ref().map(simplify), simplify(value)))
+                .collect(),
+        )),
+        other => other.clone(),
+    }
+}
+
+// exact fractions as long as they fit into `i64`, floats otherwise
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Coef {
+    Ratio(i64, i64),
+    Real(f64),
+}
+
+impl Coef {
+    fn int(n: i64) -> Self {
+        Coef::Ratio(n, 1)
+    }
+
+    // the reduced fraction with a positive denominator. normalizing is done in `i128` because
+    // negating `i64::min_value()` overflows; fractions that do not fit back become floats.
+    fn ratio(n: i64, d: i64) -> Self {
+        fn gcd(a: i128, b: i128) -> i128 {
+            if b == 0 {
+                a.abs()
+            } else {
+                gcd(b, a % b)
+            }
+        }
+        let (n, d) = (i128::from(n), i128::from(d));
+        let g = gcd(n, d).max(1);
+        let sign = if d < 0 { -1 } else { 1 };
+        let (n, d) = (sign * n / g, sign * d / g);
+        match (i64::try_from(n), i64::try_from(d)) {
+            (Ok(n), Ok(d)) => Coef::Ratio(n, d),
+            _ => Coef::Real(n as f64 / d as f64),
+        }
+    }
+
+    fn from_num(n: &NumType) -> Self {
+        match n {
+            NumType::Natural(n) => Coef::int(*n),
+            NumType::Rational(n) => Coef::Real(*n),
+        }
+    }
+
+    fn as_f64(self) -> f64 {
+        match self {
+            Coef::Ratio(n, d) => n as f64 / d as f64,
+            Coef::Real(n) => n,
+        }
+    }
+
+    fn is(self, n: i64) -> bool {
+        match self {
+            Coef::Ratio(a, b) => a == n && b == 1,
+            Coef::Real(a) => a == n as f64,
+        }
+    }
+
+    fn is_integer(self) -> bool {
+        match self {
+            Coef::Ratio(_, d) => d == 1,
+            Coef::Real(n) => n.fract() == 0.,
+        }
+    }
+
+    fn is_negative(self) -> bool {
+        self.as_f64() < 0.
+    }
+
+    fn add(self, other: Self) -> Self {
+        match (self, other) {
+            (Coef::Ratio(a, b), Coef::Ratio(c, d)) => {
+                match (a.checked_mul(d), c.checked_mul(b), b.checked_mul(d)) {
+                    (Some(ad), Some(cb), Some(bd)) => match ad.checked_add(cb) {
+                        Some(n) => Coef::ratio(n, bd),
+                        _ => Coef::Real(self.as_f64() + other.as_f64()),
+                    },
+                    _ => Coef::Real(self.as_f64() + other.as_f64()),
+                }
+            }
+            _ => Coef::Real(self.as_f64() + other.as_f64()),
+        }
+    }
+
+    fn mul(self, other: Self) -> Self {
+        match (self, other) {
+            (Coef::Ratio(a, b), Coef::Ratio(c, d)) => match (a.checked_mul(c), b.checked_mul(d)) {
+                (Some(n), Some(d)) => Coef::ratio(n, d),
+                _ => Coef::Real(self.as_f64() * other.as_f64()),
+            },
+            _ => Coef::Real(self.as_f64() * other.as_f64()),
+        }
+    }
+
+    fn neg(self) -> Self {
+        self.mul(Coef::int(-1))
+    }
+
+    // `None` if the inverse does not exist
+    fn recip(self) -> Option<Self> {
+        match self {
+            Coef::Ratio(0, _) => None,
+            Coef::Ratio(n, d) => Some(Coef::ratio(d, n)),
+            Coef::Real(n) if n == 0. => None,
+            Coef::Real(n) => Some(Coef::Real(1. / n)),
+        }
+    }
+
+    // `None` if the power cannot be represented exactly
+    fn pow(self, exp: Self) -> Option<Self> {
+        match (self, exp) {
+            (Coef::Ratio(1, 1), _) => Some(self),
+            (Coef::Ratio(n, d), Coef::Ratio(e, 1)) if e >= 0 && e <= u32::max_value() as i64 => {
+                match (n.checked_pow(e as u32), d.checked_pow(e as u32)) {
+                    (Some(n), Some(d)) => Some(Coef::ratio(n, d)),
+                    _ => None,
+                }
+            }
+            (Coef::Ratio(_, _), Coef::Ratio(e, 1)) if e < 0 => {
+                self.recip()?.pow(Coef::int(e.checked_neg()?))
+            }
+            (Coef::Real(n), Coef::Ratio(e, 1)) => Some(Coef::Real(n.powf(e as f64))),
+            _ => None,
+        }
+    }
+
+    fn into_expr(self) -> Expr {
+        match self {
+            Coef::Ratio(n, 1) => Expr::Value(Value::Numeric(NumType::Natural(n))),
+            Coef::Ratio(n, d) => Expr::Comp(
+                Operator::Div,
+                Box::new(Coef::int(n).into_expr()),
+                Box::new(Coef::int(d).into_expr()),
+            ),
+            Coef::Real(n) => Expr::Value(Value::Numeric(NumType::Rational(n))),
+        }
+    }
+
+    // exponents must not use integer division when written back
+    fn into_exponent(self) -> Expr {
+        match self {
+            Coef::Ratio(_, d) if d != 1 => Coef::Real(self.as_f64()).into_expr(),
+            _ => self.into_expr(),
+        }
+    }
+}
+
+// `coef * base0 ^ exp0 * base1 ^ exp1 ...`
+#[derive(Clone, Debug)]
+struct Product {
+    coef: Coef,
+    factors: Vec<(Expr, Coef)>,
+}
+
+impl Product {
+    fn constant(coef: Coef) -> Self {
+        Self {
+            coef,
+            factors: vec![],
+        }
+    }
+
+    fn atom(expr: Expr) -> Self {
+        Self {
+            coef: Coef::int(1),
+            factors: vec![(expr, Coef::int(1))],
+        }
+    }
+
+    fn mul(mut self, other: Product) -> Self {
+        self.coef = self.coef.mul(other.coef);
+        for (base, exp) in other.factors.into_iter() {
+            match self.factors.iter_mut().find(|(b, _)| *b == base) {
+                Some(factor) => factor.1 = factor.1.add(exp),
+                _ => self.factors.push((base, exp)),
+            }
+        }
+        // cancel common factors
+        self.factors.retain(|(_, exp)| !exp.is(0));
+        self
+    }
+
+    // `None` if the power cannot be applied to every factor
+    fn pow(self, exp: Coef) -> Option<Self> {
+        if !exp.is_integer() && !self.factors.is_empty() {
+            return None;
+        }
+        let coef = self.coef.pow(exp)?;
+        let factors = self
+            .factors
+            .into_iter()
+            .map(|(base, e)| (base, e.mul(exp)))
+            .collect();
+        Some(Self { coef, factors })
+    }
+
+    fn recip(self) -> Option<Self> {
+        self.pow(Coef::int(-1))
+    }
+
+    // true if both products have the same factors regardless of their order
+    fn is_like(&self, other: &Product) -> bool {
+        self.factors.len() == other.factors.len()
+            && self.factors.iter().all(|f| other.factors.contains(f))
+    }
+
+    fn into_expr(self) -> Expr {
+        let (numerator, denominator): (Vec<_>, Vec<_>) = self
+            .factors
+            .into_iter()
+            .partition(|(_, exp)| !exp.is_negative());
+
+        let (coef_num, coef_den) = match self.coef {
+            Coef::Ratio(n, d) => (Coef::int(n), Coef::int(d)),
+            coef => (coef, Coef::int(1)),
+        };
+
+        // a float coefficient of a quotient is kept because it turns the division into a
+        // float division
+        let implicit = match coef_num {
+            Coef::Ratio(1, 1) => true,
+            coef => coef.is(1) && denominator.is_empty(),
+        };
+        let mut num = if implicit && !numerator.is_empty() {
+            None
+        } else {
+            Some(coef_num.into_expr())
+        };
+        for (base, exp) in numerator.into_iter() {
+            num = Some(join(Operator::Mul, num, power(base, exp)));
+        }
+
+        let mut den = if coef_den.is(1) {
+            None
+        } else {
+            Some(coef_den.into_expr())
+        };
+        for (base, exp) in denominator.into_iter() {
+            den = Some(join(Operator::Mul, den, power(base, exp.neg())));
+        }
+
+        let num = num.unwrap_or_else(|| Coef::int(1).into_expr());
+        match den {
+            Some(den) => Expr::Comp(Operator::Div, Box::new(num), Box::new(den)),
+            _ => num,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Sum(Vec<Product>);
+
+impl Sum {
+    fn from_expr(expr: &Expr) -> Self {
+        match expr {
+            Expr::Value(Value::Numeric(n)) => Sum(vec![Product::constant(Coef::from_num(n))]),
+            Expr::Comp(Operator::Add, lhs, rhs) => Sum::from_expr(lhs).add(Sum::from_expr(rhs)),
+            Expr::Comp(Operator::Sub, lhs, rhs) => {
+                Sum::from_expr(lhs).add(Sum::from_expr(rhs).neg())
+            }
+            Expr::Comp(Operator::Mul, lhs, rhs) => Sum::from_expr(lhs).mul(Sum::from_expr(rhs)),
+            Expr::Comp(Operator::Div, lhs, rhs) => {
+                let rhs = Sum::from_expr(rhs);
+                match rhs.single().and_then(Product::recip) {
+                    Some(recip) => Sum::from_expr(lhs).mul(Sum(vec![recip])),
+                    _ => {
+                        let denominator = Product::atom(rhs.into_expr()).recip().unwrap();
+                        Sum::from_expr(lhs).mul(Sum(vec![denominator]))
+                    }
+                }
+            }
+            Expr::Comp(Operator::Pow, base, exp) => {
+                let base = Sum::from_expr(base);
+                let exp = Sum::from_expr(exp);
+                match exp.constant() {
+                    // a power of a single product can be applied to every factor
+                    Some(e) => match base.single().and_then(|p| p.pow(e)) {
+                        Some(product) => Sum(vec![product]),
+                        _ if e.is(0) => Sum(vec![Product::constant(Coef::int(1))]),
+                        _ => Sum(vec![Product {
+                            coef: Coef::int(1),
+                            factors: vec![(base.into_expr(), e)],
+                        }]),
+                    },
+                    _ => Sum(vec![Product::atom(Expr::Comp(
+                        Operator::Pow,
+                        Box::new(base.into_expr()),
+                        Box::new(exp.into_expr()),
+                    ))]),
+                }
+            }
+            other => Sum(vec![Product::atom(simplify(other))]),
+        }
+    }
+
+    fn add(mut self, other: Sum) -> Self {
+        for product in other.0.into_iter() {
+            match self.0.iter_mut().find(|p| p.is_like(&product)) {
+                Some(like) => like.coef = like.coef.add(product.coef),
+                _ => self.0.push(product),
+            }
+        }
+        self.0.retain(|p| !p.coef.is(0));
+        self
+    }
+
+    fn neg(self) -> Self {
+        Sum(self
+            .0
+            .into_iter()
+            .map(|mut p| {
+                p.coef = p.coef.neg();
+                p
+            })
+            .collect())
+    }
+
+    // only constant factors are distributed over sums, other sums are kept as a whole
+    fn mul(self, other: Sum) -> Self {
+        match (self.constant(), other.constant()) {
+            (Some(c), _) => other.scale(c),
+            (_, Some(c)) => self.scale(c),
+            _ => match (self.single(), other.single()) {
+                (Some(a), Some(b)) => Sum(vec![a.mul(b)]),
+                (Some(a), None) => Sum(vec![a.mul(Product::atom(other.into_expr()))]),
+                (None, Some(b)) => Sum(vec![Product::atom(self.into_expr()).mul(b)]),
+                (None, None) => Sum(vec![
+                    Product::atom(self.into_expr()).mul(Product::atom(other.into_expr()))
+                ]),
+            },
+        }
+    }
+
+    fn scale(self, c: Coef) -> Self {
+        let scaled = Sum(self
+            .0
+            .into_iter()
+            .map(|mut p| {
+                p.coef = p.coef.mul(c);
+                p
+            })
+            .collect());
+        Sum(vec![]).add(scaled)
+    }
+
+    fn single(&self) -> Option<Product> {
+        match self.0.as_slice() {
+            [product] => Some(product.clone()),
+            [] => Some(Product::constant(Coef::int(0))),
+            _ => None,
+        }
+    }
+
+    fn constant(&self) -> Option<Coef> {
+        match self.single() {
+            Some(ref product) if product.factors.is_empty() => Some(product.coef),
+            _ => None,
+        }
+    }
+
+    fn into_expr(self) -> Expr {
+        let mut result: Option<Expr> = None;
+        for product in self.0.into_iter() {
+            result = Some(match result {
+                Some(lhs) if product.coef.is_negative() => {
+                    let mut product = product;
+                    product.coef = product.coef.neg();
+                    Expr::Comp(Operator::Sub, Box::new(lhs), Box::new(product.into_expr()))
+                }
+                Some(lhs) => {
+                    Expr::Comp(Operator::Add, Box::new(lhs), Box::new(product.into_expr()))
+                }
+                _ => product.into_expr(),
+            });
+        }
+        result.unwrap_or_else(|| Coef::int(0).into_expr())
+    }
+}
+
+fn join(op: Operator, lhs: Option<Expr>, rhs: Expr) -> Expr {
+    match lhs {
+        Some(lhs) => Expr::Comp(op, Box::new(lhs), Box::new(rhs)),
+        _ => rhs,
+    }
+}
+
+fn power(base: Expr, exp: Coef) -> Expr {
+    if exp.is(1) {
+        base
+    } else {
+        Expr::Comp(Operator::Pow, Box::new(base), Box::new(exp.into_exponent()))
+    }
+}
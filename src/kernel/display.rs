@@ -0,0 +1,106 @@
+use super::*;
+
+// structured and rich representations of results. every value is sent as tree that keeps
+// the difference between integers and floats; tuples of rows and sets with keys are
+// additionally rendered as html table.
+
+pub fn value_tree(value: &ast::Value) -> Value {
+    match value {
+        ast::Value::Nil => json!({"type": "nil"}),
+        ast::Value::Numeric(NumType::Natural(n)) => json!({"type": "int", "value": n}),
+        // json has no representation of infinity and nan
+        ast::Value::Numeric(NumType::Rational(n)) if !n.is_finite() => {
+            json!({"type": "float", "value": n.to_string()})
+        }
+        ast::Value::Numeric(NumType::Rational(n)) => json!({"type": "float", "value": n}),
+        ast::Value::Logical(t) => json!({"type": "bool", "value": t}),
+        ast::Value::Str(s) => json!({"type": "str", "value": s.to_string()}),
+        ast::Value::Tuple(items) => {
+            let items = items.iter().map(expr_tree).collect::<Vec<_>>();
+            json!({"type": "tuple", "items": items})
+        }
+        ast::Value::Set(entries) => {
+            let entries = entries
+                .iter()
+                .map(|(key, value)| {
+                    let key = key.as_ref().map_or(Value::Null, expr_tree);
+                    json!({"key": key, "value": expr_tree(value)})
+                })
+                .collect::<Vec<_>>();
+            json!({"type": "set", "entries": entries})
+        }
+    }
+}
+
+fn expr_tree(expr: &Expr) -> Value {
+    match expr {
+        Expr::Value(value) => value_tree(value),
+        other => json!({"type": "expr", "source": other.to_string()}),
+    }
+}
+
+// mime type and content of every rendering available for `value`
+pub fn renderings(value: &ast::Value) -> Vec<(&'static str, String)> {
+    let mut renderings = vec![];
+    if let Some(table) = table(value) {
+        renderings.push(("text/html", table));
+    }
+    renderings
+}
+
+// cells of a tuple of equally long tuples or of a set where every item has a key
+fn rows(value: &ast::Value) -> Option<Vec<Vec<&Expr>>> {
+    match value {
+        ast::Value::Tuple(items) if !items.is_empty() => {
+            let rows = items
+                .iter()
+                .map(|item| match item {
+                    Expr::Value(ast::Value::Tuple(row)) if !row.is_empty() => {
+                        Some(row.iter().collect::<Vec<_>>())
+                    }
+                    _ => None,
+                })
+                .collect::<Option<Vec<_>>>()?;
+            if rows.iter().any(|row| row.len() != rows[0].len()) {
+                return None;
+            }
+            Some(rows)
+        }
+        ast::Value::Set(entries) if !entries.is_empty() => entries
+            .iter()
+            .map(|(key, value)| key.as_ref().map(|key| vec![key, value]))
+            .collect(),
+        _ => None,
+    }
+}
+
+fn table(value: &ast::Value) -> Option<String> {
+    let rows = rows(value)?;
+    let mut html = String::from("<table>");
+    for row in rows.iter() {
+        html.push_str("<tr>");
+        for cell in row.iter() {
+            html.push_str("<td>");
+            html.push_str(&escape_html(&cell.to_string()));
+            html.push_str("</td>");
+        }
+        html.push_str("</tr>");
+    }
+    html.push_str("</table>");
+    Some(html)
+}
+
+fn escape_html(s: &str) -> String {
+    let mut escaped = String::new();
+    for c in s.chars() {
+        match c {
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '&' => escaped.push_str("&amp;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
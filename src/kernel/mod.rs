@@ -0,0 +1,318 @@
+pub mod display;
+
+use super::*;
+
+use crate::repl::script::statements;
+
+use localc_cc_lib::formatter::is_ident_char;
+use lovm::vm;
+use serde_json::{json, Value};
+
+use std::io::{self, BufRead, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+
+// notebook kernel around a persistent repl. every line of input is a json request, every
+// line of output the reply to one request:
+//
+//  {"id": 1, "type": "execute", "code": "f(x) = x ^ 2\nf(3)"}
+//  {"id": 2, "type": "complete", "code": "fa", "cursor": 2}
+//  {"id": 3, "type": "inspect", "code": "fac(3)", "cursor": 1}
+//  {"id": 4, "type": "interrupt"}
+//
+// replies carry the `id` of their request and a `status` of either `ok` or `error`. an
+// interrupt is seen as soon as it is read: the running execution stops before its next
+// function call, statement or output and queued executions fail until the interrupt itself
+// is answered.
+
+// functions whose output is captured
+const CAPTURED: &[&str] = &["println", "print"];
+
+pub struct Kernel {
+    pub repl: Repl,
+    // output of `println` and `print` during the current execution
+    stdout: Arc<Mutex<String>>,
+    interrupted: Arc<AtomicBool>,
+    // number of executions, used to name cells in errors
+    count: usize,
+}
+
+fn reply(id: &Value, mut content: Value) -> Value {
+    content["id"] = id.clone();
+    content
+}
+
+fn error(kind: &str, message: &str) -> Value {
+    json!({"status": "error", "error": {"kind": kind, "message": message}})
+}
+
+// byte range of the identifier around `cursor`, which is counted in characters
+fn ident_around(code: &str, cursor: usize) -> (usize, usize) {
+    let cursor = code
+        .char_indices()
+        .nth(cursor)
+        .map_or(code.len(), |(idx, _)| idx);
+    let start = code[..cursor]
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| is_ident_char(*c))
+        .last()
+        .map_or(cursor, |(idx, _)| idx);
+    let end = code[cursor..]
+        .char_indices()
+        .find(|(_, c)| !is_ident_char(*c))
+        .map_or(code.len(), |(idx, _)| cursor + idx);
+    (start, end)
+}
+
+impl Kernel {
+    pub fn new(repl: Repl) -> Self {
+        let mut kernel = Self {
+            repl,
+            stdout: Arc::new(Mutex::new(String::new())),
+            interrupted: Arc::new(AtomicBool::new(false)),
+            count: 0,
+        };
+        kernel.capture_output();
+        kernel
+    }
+
+    // flag that stops the running execution when set
+    pub fn interrupter(&self) -> Arc<AtomicBool> {
+        self.interrupted.clone()
+    }
+
+    // replace the printing functions with natives writing into `stdout` and make running
+    // code stop on interrupts
+    fn capture_output(&mut self) {
+        for name in CAPTURED.iter() {
+            let newline = *name == "println";
+            let stdout = self.stdout.clone();
+            let interrupted = self.interrupted.clone();
+            let native = move |data: &mut vm::VmData, args: &[lovm::Value]| {
+                if interrupted.load(Ordering::SeqCst) {
+                    return Err(runtime::INTERRUPTED.to_string());
+                }
+                let text = args
+                    .iter()
+                    .map(|arg| match arg {
+                        lovm::Value::Str(s) => s.to_string(),
+                        other => Pretty::new().format(data, other),
+                    })
+                    .collect::<Vec<_>>();
+                let mut stdout = stdout.lock().unwrap();
+                stdout.push_str(&text.join(" "));
+                if newline {
+                    stdout.push('\n');
+                }
+                Ok(lovm::Value::T(true))
+            };
+            self.repl
+                .runtime
+                .define_variadic(&name.to_string(), Arc::new(native), false);
+        }
+        self.repl.runtime.set_interrupt(self.interrupted.clone());
+    }
+
+    pub fn handle(&mut self, request: &Value) -> Value {
+        let id = request.get("id").cloned().unwrap_or(Value::Null);
+        let code = request["code"].as_str().unwrap_or("");
+        // the cursor defaults to the end of code
+        let cursor = request["cursor"]
+            .as_u64()
+            .map_or(code.chars().count(), |cursor| cursor as usize);
+
+        let content = match request["type"].as_str() {
+            Some("execute") => self.execute(code),
+            Some("complete") => self.complete(code, cursor),
+            Some("inspect") => self.inspect(code, cursor),
+            Some("interrupt") => {
+                self.interrupted.store(false, Ordering::SeqCst);
+                json!({"status": "ok"})
+            }
+            Some(other) => error("protocol", &format!("unknown request type `{}`", other)),
+            _ => error("protocol", "request has no type"),
+        };
+
+        reply(&id, content)
+    }
+
+    pub fn execute(&mut self, code: &str) -> Value {
+        self.count += 1;
+        self.stdout.lock().unwrap().clear();
+
+        let mut content = if command::is_command(code) {
+            self.execute_command(code)
+        } else {
+            self.execute_code(code)
+        };
+
+        content["stdout"] = Value::String(self.stdout.lock().unwrap().clone());
+        content
+    }
+
+    fn execute_command(&mut self, line: &str) -> Value {
+        let result = self.repl.command(line);
+        // a reset drops the natives capturing output
+        if self.repl.runtime.function(CAPTURED[0]).is_none() {
+            self.capture_output();
+        }
+        match result {
+            Ok(output) => json!({"status": "ok", "result": null, "data": {"text/plain": output}}),
+            Err(err) => error("command", &err),
+        }
+    }
+
+    fn execute_code(&mut self, code: &str) -> Value {
+        let file = format!("cell[{}]", self.count);
+        let mut result = None;
+
+        for stmt in statements(code).iter() {
+            if self.interrupted.load(Ordering::SeqCst) {
+                let (line, col) = stmt.start();
+                return json!({
+                    "status": "error",
+                    "error": {
+                        "kind": "interrupted",
+                        "message": runtime::INTERRUPTED,
+                        "file": file,
+                        "statement": stmt.src.trim(),
+                        "span": {
+                            "start": {"line": line, "col": col},
+                            "end": {"line": line, "col": col},
+                        },
+                    },
+                });
+            }
+
+            result = match self.repl.run_statement(&file, stmt) {
+                Ok(result) => result,
+                Err(err) => {
+                    let mut details = err.to_json();
+                    if self.interrupted.load(Ordering::SeqCst) {
+                        details["kind"] = json!("interrupted");
+                    }
+                    return json!({"status": "error", "error": details});
+                }
+            };
+
+            // printing is done for its output, not for its result
+            if let Ok(Expr::Func(name, _)) = self.repl.parser.parse(&stmt.src) {
+                if CAPTURED.contains(&name.as_str()) {
+                    result = None;
+                }
+            }
+        }
+
+        if let Err(err) = self.repl.runtime.sync() {
+            return error("runtime", &err);
+        }
+
+        match result {
+            Some(value) => self.result(&value),
+            _ => json!({"status": "ok", "result": null, "data": {}}),
+        }
+    }
+
+    fn result(&self, value: &lovm::Value) -> Value {
+        let mut data = json!({"text/plain": self.repl.format(value)});
+        // values without localc equivalent are only sent as text
+        let tree = match self.repl.runtime.to_value(value) {
+            Ok(value) => {
+                for (mime, rendering) in display::renderings(&value) {
+                    data[mime] = Value::String(rendering);
+                }
+                display::value_tree(&value)
+            }
+            _ => Value::Null,
+        };
+        json!({"status": "ok", "result": tree, "data": data})
+    }
+
+    // names starting with the identifier before `cursor`
+    pub fn complete(&self, code: &str, cursor: usize) -> Value {
+        let (start, _) = ident_around(code, cursor);
+        let end = code
+            .char_indices()
+            .nth(cursor)
+            .map_or(code.len(), |(idx, _)| idx);
+        let prefix = &code[start..end];
+        let matches = self
+            .repl
+            .completions()
+            .into_iter()
+            .filter(|name| name.starts_with(prefix))
+            .collect::<Vec<_>>();
+        json!({
+            "status": "ok",
+            "matches": matches,
+            "cursor_start": code[..start].chars().count(),
+            "cursor_end": cursor,
+        })
+    }
+
+    // overloads of the function or value of the global under `cursor`
+    pub fn inspect(&self, code: &str, cursor: usize) -> Value {
+        let (start, end) = ident_around(code, cursor);
+        let name = &code[start..end];
+        let text = match self.repl.show(name) {
+            Ok(overloads) => Some(overloads),
+            _ => self
+                .repl
+                .runtime
+                .globals()
+                .into_iter()
+                .find(|(global, _)| global.as_str() == name)
+                .map(|(_, value)| format!("{} = {}", name, self.repl.format(value))),
+        };
+        match text {
+            Some(text) => json!({"status": "ok", "found": true, "data": {"text/plain": text}}),
+            _ => json!({"status": "ok", "found": false, "data": {}}),
+        }
+    }
+
+    // answer requests read from `input` until it is closed. requests are read on a separate
+    // thread so interrupts are noticed while code is running.
+    pub fn serve<R, W>(&mut self, input: R, output: &mut W) -> io::Result<()>
+    where
+        R: BufRead + Send + 'static,
+        W: Write,
+    {
+        let (sender, receiver) = mpsc::channel();
+        let interrupted = self.interrupter();
+
+        let reader = thread::spawn(move || {
+            for line in input.lines() {
+                let line = match line {
+                    Ok(line) => line,
+                    _ => break,
+                };
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let request = serde_json::from_str::<Value>(&line).map_err(|err| err.to_string());
+                if let Ok(request) = request.as_ref() {
+                    if request["type"] == "interrupt" {
+                        interrupted.store(true, Ordering::SeqCst);
+                    }
+                }
+                if sender.send(request).is_err() {
+                    break;
+                }
+            }
+        });
+
+        for request in receiver.iter() {
+            let content = match request {
+                Ok(request) => self.handle(&request),
+                Err(err) => reply(&Value::Null, error("protocol", &err)),
+            };
+            writeln!(output, "{}", content)?;
+            output.flush()?;
+        }
+
+        reader.join().expect("kernel input thread panicked");
+        Ok(())
+    }
+}
@@ -2,18 +2,23 @@
 #![feature(box_syntax)]
 #![allow(clippy::all)]
 
+extern crate atty;
 extern crate env_logger;
 extern crate lazy_static;
 extern crate log;
 extern crate rand;
 extern crate regex;
+extern crate rustyline;
+extern crate serde_json;
 
+pub mod kernel;
+pub mod lsp;
 pub mod repl;
 pub mod runtime;
 #[macro_use]
 pub mod test;
 
-use localc_cc_lib::{ast::*, compiler, expr::*};
+use localc_cc_lib::{ast, ast::*, compiler, diff, disasm, expr::*, optimize, simplify};
 use repl::*;
 use runtime::*;
 
@@ -0,0 +1,349 @@
+use super::*;
+
+use crate::repl::script::{statements, Statement};
+use crate::runtime::builtin::collection::CALLBACKS;
+
+use localc_cc_lib::formatter::is_ident_char;
+
+use std::collections::HashSet;
+
+// static analysis of a document. statements are parsed but never executed; names are
+// resolved against the definitions in the document and the symbols of a runtime.
+
+// functions handled by the repl itself or provided by lovm
+const INTRINSICS: &[&str] = &["diff", "memo", "simplify", "println", "print"];
+// intrinsics that take names of functions and variables instead of values
+const NAME_ARGS: &[&str] = &["diff", "memo"];
+
+// positions are zero based like in the language server protocol. `character` counts utf-16
+// code units.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Position {
+    pub line: usize,
+    pub character: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Range {
+    pub start: Position,
+    pub end: Position,
+}
+
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    pub range: Range,
+    pub message: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SymbolKind {
+    Function,
+    Global,
+}
+
+#[derive(Clone, Debug)]
+pub struct Definition {
+    pub name: Name,
+    pub kind: SymbolKind,
+    // the whole defining statement
+    pub range: Range,
+    // left hand side of the definition, e.g. `f(x, 0)`
+    pub signature: String,
+    pub source: String,
+}
+
+pub struct Document {
+    pub text: String,
+    pub definitions: Vec<Definition>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+// statement positions are one based and count bytes
+fn position(stmt: &Statement, offset: usize) -> Position {
+    let (line, col) = stmt.position(offset);
+    let line_start = offset - (col - 1);
+    let mut bytes = line_start;
+    let mut character = 0;
+    for c in stmt.src[line_start..].chars() {
+        if offset <= bytes {
+            break;
+        }
+        bytes += c.len_utf8();
+        character += c.len_utf16();
+    }
+    // offsets behind the end of the statement
+    character += offset.saturating_sub(bytes);
+    Position {
+        line: line - 1,
+        character,
+    }
+}
+
+fn span(stmt: &Statement, offset: usize, len: usize) -> Range {
+    Range {
+        start: position(stmt, offset),
+        end: position(stmt, offset + len),
+    }
+}
+
+// byte offset of the first occurrence of `name` as whole identifier in `src`
+fn find_ident(src: &str, name: &str) -> Option<usize> {
+    src.match_indices(name)
+        .find(|(idx, _)| {
+            let before = src[..*idx]
+                .chars()
+                .next_back()
+                .map_or(true, |c| !is_ident_char(c));
+            let after = src[idx + name.len()..]
+                .chars()
+                .next()
+                .map_or(true, |c| !is_ident_char(c));
+            before && after
+        })
+        .map(|(idx, _)| idx)
+}
+
+impl Document {
+    pub fn analyze(text: &str, runtime: &Runtime) -> Self {
+        let parser = ExprParser::new();
+        let mut parsed = vec![];
+        let mut definitions = vec![];
+        let mut diagnostics = vec![];
+
+        for stmt in statements(text).into_iter() {
+            let start = stmt.src.len() - stmt.src.trim_start().len();
+            let range = Range {
+                start: position(&stmt, start),
+                end: position(&stmt, stmt.src.trim_end().len()),
+            };
+
+            let ast = match parser.parse(&stmt.src) {
+                Ok(ast) => ast,
+                Err(err) => {
+                    let offset =
+                        localc_cc_lib::error_offset(&err).unwrap_or(stmt.src.trim_end().len());
+                    diagnostics.push(Diagnostic {
+                        range: span(&stmt, offset, 1),
+                        message: format!("parse error: {:?}", err),
+                    });
+                    continue;
+                }
+            };
+
+            let definition = match &ast {
+                Expr::Comp(Operator::Store, lhs, _) => match lhs.as_ref() {
+                    Expr::Func(name, _) => Some((name.clone(), SymbolKind::Function, lhs)),
+                    Expr::Ref(name) => Some((name.clone(), SymbolKind::Global, lhs)),
+                    _ => None,
+                },
+                _ => None,
+            };
+            if let Some((name, kind, lhs)) = definition {
+                definitions.push(Definition {
+                    name,
+                    kind,
+                    range,
+                    signature: lhs.to_string(),
+                    source: ast.to_string(),
+                });
+            }
+
+            parsed.push((stmt, ast));
+        }
+
+        let mut doc = Self {
+            text: text.to_string(),
+            definitions,
+            diagnostics,
+        };
+
+        let (functions, globals) = doc.known_names(runtime);
+        for (stmt, ast) in parsed.iter() {
+            let mut unresolved = vec![];
+            unresolved_names(ast, &[], &functions, &globals, &mut unresolved);
+            for (name, message) in unresolved.into_iter() {
+                let offset = find_ident(&stmt.src, &name).unwrap_or(0);
+                doc.diagnostics.push(Diagnostic {
+                    range: span(stmt, offset, name.len()),
+                    message,
+                });
+            }
+        }
+
+        doc
+    }
+
+    // names of all functions and globals defined in the document or the runtime
+    fn known_names(&self, runtime: &Runtime) -> (HashSet<Name>, HashSet<Name>) {
+        let mut functions = runtime
+            .functions()
+            .into_iter()
+            .cloned()
+            .collect::<HashSet<_>>();
+        functions.extend(INTRINSICS.iter().map(|name| name.to_string()));
+        functions.extend(CALLBACKS.iter().map(|(name, _)| name.to_string()));
+        let mut globals = runtime
+            .globals()
+            .into_iter()
+            .map(|(name, _)| name.clone())
+            .collect::<HashSet<_>>();
+        for def in self.definitions.iter() {
+            match def.kind {
+                SymbolKind::Function => functions.insert(def.name.clone()),
+                SymbolKind::Global => globals.insert(def.name.clone()),
+            };
+        }
+        (functions, globals)
+    }
+
+    // the identifier the cursor is placed on or directly behind
+    pub fn ident_at(&self, pos: Position) -> Option<String> {
+        let line = self.text.lines().nth(pos.line)?.chars().collect::<Vec<_>>();
+        // index of the char that starts at utf-16 offset `pos.character`
+        let mut units = 0;
+        let cursor = line
+            .iter()
+            .position(|c| {
+                units += c.len_utf16();
+                pos.character < units
+            })
+            .unwrap_or(line.len());
+        let start = (0..cursor)
+            .rev()
+            .take_while(|i| is_ident_char(line[*i]))
+            .last()
+            .unwrap_or(cursor);
+        let end = (cursor..line.len())
+            .take_while(|i| is_ident_char(line[*i]))
+            .last()
+            .map_or(cursor, |i| i + 1);
+        if start == end {
+            return None;
+        }
+        Some(line[start..end].iter().collect())
+    }
+
+    pub fn hover(&self, pos: Position, runtime: &Runtime) -> Option<String> {
+        let name = self.ident_at(pos)?;
+        let defined = self
+            .definitions
+            .iter()
+            .filter(|def| def.name == name)
+            .map(|def| def.source.clone())
+            .collect::<Vec<_>>();
+        if !defined.is_empty() {
+            return Some(defined.join("\n"));
+        }
+        if let Some(function) = runtime.function(&name) {
+            let lines = function
+                .to_string()
+                .lines()
+                .map(|line| format!("{}{}", name, line))
+                .collect::<Vec<_>>();
+            return Some(lines.join("\n"));
+        }
+        runtime
+            .globals()
+            .into_iter()
+            .find(|(global, _)| **global == name)
+            .map(|(_, value)| format!("{} = {}", name, runtime.pretty(value, &Pretty::new())))
+    }
+
+    // range of the first statement defining the name under the cursor
+    pub fn definition(&self, pos: Position) -> Option<Range> {
+        let name = self.ident_at(pos)?;
+        self.definitions
+            .iter()
+            .find(|def| def.name == name)
+            .map(|def| def.range)
+    }
+
+    // all known names and their kind in alphabetical order
+    pub fn completions(&self, runtime: &Runtime) -> Vec<(Name, SymbolKind)> {
+        let (functions, globals) = self.known_names(runtime);
+        let mut names = functions
+            .into_iter()
+            .map(|name| (name, SymbolKind::Function))
+            .chain(globals.into_iter().map(|name| (name, SymbolKind::Global)))
+            .collect::<Vec<_>>();
+        names.sort_by(|a, b| a.0.cmp(&b.0));
+        names
+    }
+}
+
+// collect names that are neither parameters nor defined anywhere
+fn unresolved_names(
+    expr: &Expr,
+    params: &[Name],
+    functions: &HashSet<Name>,
+    globals: &HashSet<Name>,
+    unresolved: &mut Vec<(Name, String)>,
+) {
+    let check = |expr: &Expr, params: &[Name], unresolved: &mut Vec<(Name, String)>| {
+        unresolved_names(expr, params, functions, globals, unresolved)
+    };
+
+    match expr {
+        Expr::Comp(Operator::Store, lhs, body) => match lhs.as_ref() {
+            Expr::Func(_, args) => {
+                let params = args
+                    .iter()
+                    .filter_map(|arg| match arg {
+                        Expr::Ref(name) => Some(name.clone()),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>();
+                check(body, &params, unresolved);
+            }
+            _ => check(body, params, unresolved),
+        },
+        Expr::Comp(_, lhs, rhs) => {
+            check(lhs, params, unresolved);
+            check(rhs, params, unresolved);
+        }
+        Expr::Func(name, _) if NAME_ARGS.contains(&name.as_str()) => {}
+        // the callback of `map`, `filter` and `fold` is the name of a function
+        Expr::Func(name, args) if CALLBACKS.iter().any(|(kind, _)| *kind == name.as_str()) => {
+            let args = match args.split_first() {
+                Some((Expr::Ref(callback), rest)) => {
+                    if !functions.contains(callback) {
+                        unresolved
+                            .push((callback.clone(), format!("unknown function `{}`", callback)));
+                    }
+                    rest
+                }
+                _ => &args[..],
+            };
+            for arg in args.iter() {
+                check(arg, params, unresolved);
+            }
+        }
+        Expr::Func(name, args) => {
+            if !functions.contains(name) {
+                unresolved.push((name.clone(), format!("unknown function `{}`", name)));
+            }
+            for arg in args.iter() {
+                check(arg, params, unresolved);
+            }
+        }
+        Expr::Ref(name) => {
+            if !params.contains(name) && !globals.contains(name) {
+                unresolved.push((name.clone(), format!("unknown name `{}`", name)));
+            }
+        }
+        Expr::Value(ast::Value::Tuple(items)) => {
+            for item in items.iter() {
+                check(item, params, unresolved);
+            }
+        }
+        Expr::Value(ast::Value::Set(items)) => {
+            for (key, value) in items.iter() {
+                if let Some(key) = key {
+                    check(key, params, unresolved);
+                }
+                check(value, params, unresolved);
+            }
+        }
+        _ => {}
+    }
+}
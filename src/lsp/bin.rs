@@ -0,0 +1,19 @@
+use localc::lsp::Server;
+
+use std::io;
+use std::process;
+
+pub fn main() {
+    env_logger::init();
+
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let mut server = Server::new();
+
+    if let Err(err) = server.serve(&mut stdin.lock(), &mut stdout.lock()) {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+
+    process::exit(server.exit_code());
+}
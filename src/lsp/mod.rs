@@ -0,0 +1,321 @@
+pub mod analysis;
+
+use super::*;
+
+use self::analysis::*;
+
+use serde_json::{json, Value};
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, Read, Write};
+
+// language server speaking json-rpc over a byte stream. documents are analyzed on every
+// change; the runtime of a repl with stdlib provides the names defined outside the document.
+
+// error codes defined by json-rpc
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const INVALID_REQUEST: i64 = -32600;
+
+// kinds defined by the protocol
+const SYNC_FULL: i64 = 1;
+const SEVERITY_ERROR: i64 = 1;
+const COMPLETION_FUNCTION: i64 = 3;
+const COMPLETION_VARIABLE: i64 = 6;
+const SYMBOL_FUNCTION: i64 = 12;
+const SYMBOL_VARIABLE: i64 = 13;
+
+pub struct Server {
+    pub repl: Repl,
+    documents: HashMap<String, Document>,
+    shutdown: bool,
+    exit: bool,
+}
+
+fn to_position(value: &Value) -> Option<Position> {
+    Some(Position {
+        line: value.get("line")?.as_u64()? as usize,
+        character: value.get("character")?.as_u64()? as usize,
+    })
+}
+
+fn position_json(pos: Position) -> Value {
+    json!({"line": pos.line, "character": pos.character})
+}
+
+fn range_json(range: Range) -> Value {
+    json!({"start": position_json(range.start), "end": position_json(range.end)})
+}
+
+fn response(id: &Value, result: Value) -> Value {
+    json!({"jsonrpc": "2.0", "id": id, "result": result})
+}
+
+fn error(id: &Value, code: i64, message: &str) -> Value {
+    json!({"jsonrpc": "2.0", "id": id, "error": {"code": code, "message": message}})
+}
+
+fn notification(method: &str, params: Value) -> Value {
+    json!({"jsonrpc": "2.0", "method": method, "params": params})
+}
+
+impl Server {
+    pub fn new() -> Self {
+        Self::with_repl(Repl::with_stdlib())
+    }
+
+    pub fn with_repl(repl: Repl) -> Self {
+        Self {
+            repl,
+            documents: HashMap::new(),
+            shutdown: false,
+            exit: false,
+        }
+    }
+
+    // exit code expected by the protocol
+    pub fn exit_code(&self) -> i32 {
+        if self.shutdown {
+            0
+        } else {
+            1
+        }
+    }
+
+    pub fn document(&self, uri: &str) -> Option<&Document> {
+        self.documents.get(uri)
+    }
+
+    // handle one message and return all messages that should be sent to the client
+    pub fn handle(&mut self, msg: &Value) -> Vec<Value> {
+        let method = match msg.get("method").and_then(Value::as_str) {
+            Some(method) => method,
+            // responses to requests of the server are not expected
+            _ => return vec![],
+        };
+        let params = msg.get("params").cloned().unwrap_or(Value::Null);
+
+        let id = match msg.get("id") {
+            Some(id) => id.clone(),
+            _ => return self.notify(method, &params),
+        };
+
+        if self.shutdown && method != "exit" {
+            return vec![error(&id, INVALID_REQUEST, "server is shutting down")];
+        }
+
+        match self.request(method, &params) {
+            Ok(result) => vec![response(&id, result)],
+            Err((code, message)) => vec![error(&id, code, &message)],
+        }
+    }
+
+    fn request(&mut self, method: &str, params: &Value) -> Result<Value, (i64, String)> {
+        match method {
+            "initialize" => Ok(json!({
+                "capabilities": {
+                    "textDocumentSync": SYNC_FULL,
+                    "hoverProvider": true,
+                    "definitionProvider": true,
+                    "completionProvider": {},
+                    "documentSymbolProvider": true,
+                },
+                "serverInfo": {"name": "localc-lsp", "version": env!("CARGO_PKG_VERSION")},
+            })),
+            "shutdown" => {
+                self.shutdown = true;
+                Ok(Value::Null)
+            }
+            "textDocument/hover" => {
+                let (doc, pos) = self.cursor(params)?;
+                Ok(match doc.hover(pos, &self.repl.runtime) {
+                    Some(text) => json!({
+                        "contents": {"kind": "markdown", "value": format!("```\n{}\n```", text)}
+                    }),
+                    _ => Value::Null,
+                })
+            }
+            "textDocument/definition" => {
+                let (doc, pos) = self.cursor(params)?;
+                let uri = params["textDocument"]["uri"].clone();
+                Ok(match doc.definition(pos) {
+                    Some(range) => json!({"uri": uri, "range": range_json(range)}),
+                    _ => Value::Null,
+                })
+            }
+            "textDocument/completion" => {
+                let (doc, _) = self.cursor(params)?;
+                let items = doc
+                    .completions(&self.repl.runtime)
+                    .into_iter()
+                    .map(|(name, kind)| {
+                        let kind = match kind {
+                            SymbolKind::Function => COMPLETION_FUNCTION,
+                            SymbolKind::Global => COMPLETION_VARIABLE,
+                        };
+                        json!({"label": name, "kind": kind})
+                    })
+                    .collect::<Vec<_>>();
+                Ok(Value::Array(items))
+            }
+            "textDocument/documentSymbol" => {
+                let doc = self.text_document(params)?;
+                let symbols = doc
+                    .definitions
+                    .iter()
+                    .map(|def| {
+                        let kind = match def.kind {
+                            SymbolKind::Function => SYMBOL_FUNCTION,
+                            SymbolKind::Global => SYMBOL_VARIABLE,
+                        };
+                        json!({
+                            "name": def.name,
+                            "detail": def.signature,
+                            "kind": kind,
+                            "range": range_json(def.range),
+                            "selectionRange": range_json(def.range),
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                Ok(Value::Array(symbols))
+            }
+            _ => Err((METHOD_NOT_FOUND, format!("unknown method `{}`", method))),
+        }
+    }
+
+    fn notify(&mut self, method: &str, params: &Value) -> Vec<Value> {
+        let uri = params["textDocument"]["uri"].as_str().map(str::to_string);
+        match (method, uri) {
+            ("exit", _) => {
+                self.exit = true;
+                vec![]
+            }
+            ("textDocument/didOpen", Some(uri)) => match params["textDocument"]["text"].as_str() {
+                Some(text) => self.update(uri, text),
+                _ => vec![],
+            },
+            // only full document sync is supported
+            ("textDocument/didChange", Some(uri)) => {
+                let text = params["contentChanges"]
+                    .as_array()
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change["text"].as_str());
+                match text {
+                    Some(text) => self.update(uri, text),
+                    _ => vec![],
+                }
+            }
+            ("textDocument/didClose", Some(uri)) => {
+                self.documents.remove(&uri);
+                vec![notification(
+                    "textDocument/publishDiagnostics",
+                    json!({"uri": uri, "diagnostics": []}),
+                )]
+            }
+            _ => vec![],
+        }
+    }
+
+    // analyze the new content of a document and publish its diagnostics
+    fn update(&mut self, uri: String, text: &str) -> Vec<Value> {
+        let doc = Document::analyze(text, &self.repl.runtime);
+        let diagnostics = doc
+            .diagnostics
+            .iter()
+            .map(|diag| {
+                json!({
+                    "range": range_json(diag.range),
+                    "severity": SEVERITY_ERROR,
+                    "source": "localc",
+                    "message": diag.message,
+                })
+            })
+            .collect::<Vec<_>>();
+        self.documents.insert(uri.clone(), doc);
+        vec![notification(
+            "textDocument/publishDiagnostics",
+            json!({"uri": uri, "diagnostics": diagnostics}),
+        )]
+    }
+
+    fn text_document(&self, params: &Value) -> Result<&Document, (i64, String)> {
+        let uri = params["textDocument"]["uri"]
+            .as_str()
+            .ok_or_else(|| (INVALID_PARAMS, "missing document uri".to_string()))?;
+        self.documents
+            .get(uri)
+            .ok_or_else(|| (INVALID_PARAMS, format!("unknown document `{}`", uri)))
+    }
+
+    fn cursor(&self, params: &Value) -> Result<(&Document, Position), (i64, String)> {
+        let doc = self.text_document(params)?;
+        let pos = to_position(&params["position"])
+            .ok_or_else(|| (INVALID_PARAMS, "missing position".to_string()))?;
+        Ok((doc, pos))
+    }
+
+    // serve messages from `input` until the client exits or closes the stream. malformed
+    // messages are answered with an error; only failures of the stream itself stop serving.
+    pub fn serve<R: BufRead, W: Write>(&mut self, input: &mut R, output: &mut W) -> io::Result<()> {
+        loop {
+            let msg = match read_message(input) {
+                Ok(Some(msg)) => msg,
+                Ok(None) => break,
+                Err(ref err) if err.kind() == io::ErrorKind::InvalidData => {
+                    let reply = error(&Value::Null, PARSE_ERROR, &err.to_string());
+                    write_message(output, &reply)?;
+                    continue;
+                }
+                Err(err) => return Err(err),
+            };
+            for reply in self.handle(&msg).iter() {
+                write_message(output, reply)?;
+            }
+            if self.exit {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+// read one message framed by a `Content-Length` header. returns `None` at the end of input.
+pub fn read_message<R: BufRead>(input: &mut R) -> io::Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim();
+        if header.is_empty() {
+            break;
+        }
+        if header.starts_with("Content-Length:") {
+            length = header["Content-Length:".len()..]
+                .trim()
+                .parse::<usize>()
+                .ok();
+        }
+    }
+
+    let length = length
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "missing content length"))?;
+    let mut content = vec![0; length];
+    input.read_exact(&mut content)?;
+    serde_json::from_slice(&content)
+        .map(Some)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+pub fn write_message<W: Write>(output: &mut W, msg: &Value) -> io::Result<()> {
+    let content = msg.to_string();
+    write!(
+        output,
+        "Content-Length: {}\r\n\r\n{}",
+        content.len(),
+        content
+    )?;
+    output.flush()
+}
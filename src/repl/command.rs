@@ -0,0 +1,175 @@
+use super::*;
+
+use std::fs::File;
+use std::io::{Read, Write};
+use std::time::{Duration, Instant};
+
+// meta commands start with a colon and inspect or manage the session instead of evaluating
+// localc code. every command is also available as method on `Repl`.
+
+const HELP: &str = "\
+:help              show this message
+:list              list all functions and globals
+:show f            show the overloads of `f`
+:undef f           remove the function or global `f`
+:reset             start a new session
+:load file.lc      run all lines of `file.lc`
+:save session.lc   write all definitions of this session to `session.lc`
+:time expr         evaluate `expr` and measure the time it took
+:bytecode expr     show the code generated for `expr` or function `expr`";
+
+pub fn is_command(line: &str) -> bool {
+    line.trim_start().starts_with(':')
+}
+
+// name of the function or global `expr` changes, if any
+pub(crate) fn defined_name(expr: &Expr) -> Option<Name> {
+    match expr {
+        Expr::Comp(Operator::Store, box Expr::Func(name, _), _) => Some(name.clone()),
+        Expr::Comp(Operator::Store, box Expr::Ref(name), _) => Some(name.clone()),
+        Expr::Func(name, args) => match (name.as_str(), args.as_slice()) {
+            ("memo", [Expr::Ref(fname)]) => Some(fname.clone()),
+            ("diff", [Expr::Ref(fname), _]) => Some(format!("{}'", fname)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+impl Repl {
+    // execute the meta command `line` and return its output
+    pub fn command(&mut self, line: &str) -> Result<String, String> {
+        let line = line.trim();
+        let (name, arg) = match line.find(char::is_whitespace) {
+            Some(idx) => (&line[..idx], line[idx..].trim()),
+            _ => (line, ""),
+        };
+        let expect_arg = |usage: &str| {
+            if arg.is_empty() {
+                Err(format!("usage: {}", usage))
+            } else {
+                Ok(arg)
+            }
+        };
+
+        match name {
+            ":help" => Ok(self.help().to_string()),
+            ":list" => Ok(self.list()),
+            ":show" => self.show(expect_arg(":show f")?),
+            ":undef" => {
+                self.undef(expect_arg(":undef f")?)?;
+                Ok(String::new())
+            }
+            ":reset" => {
+                self.reset();
+                Ok(String::new())
+            }
+            ":load" => match self.load_file(expect_arg(":load file.lc")?)? {
+                Some(result) => Ok(self.format(&result)),
+                _ => Ok(String::new()),
+            },
+            ":save" => {
+                self.save(expect_arg(":save session.lc")?)?;
+                Ok(String::new())
+            }
+            ":time" => {
+                let (result, elapsed) = self.time(expect_arg(":time expr")?)?;
+                match result {
+                    Some(result) => Ok(format!("{}\ntime: {:?}", self.format(&result), elapsed)),
+                    _ => Ok(format!("time: {:?}", elapsed)),
+                }
+            }
+            ":bytecode" => self.bytecode(expect_arg(":bytecode expr")?),
+            _ => Err(format!("unknown command `{}`, try `:help`", name)),
+        }
+    }
+
+    pub fn help(&self) -> &'static str {
+        HELP
+    }
+
+    // one line per function with all its overloads, followed by one line per global
+    pub fn list(&self) -> String {
+        let mut lines = vec![];
+        for name in self.runtime.functions() {
+            let overloads = self
+                .runtime
+                .function(name)
+                .unwrap()
+                .overloads()
+                .map(|overload| format!("{}{}", name, overload))
+                .collect::<Vec<_>>();
+            lines.push(overloads.join(" "));
+        }
+        for (name, value) in self.runtime.globals() {
+            lines.push(format!("{} = {}", name, self.format(value)));
+        }
+        lines.join("\n")
+    }
+
+    pub fn show(&self, name: &str) -> Result<String, String> {
+        let function = self
+            .runtime
+            .function(name)
+            .ok_or_else(|| format!("function `{}` is not defined", name))?;
+        let lines = function
+            .to_string()
+            .lines()
+            .map(|line| format!("{}{}", name, line))
+            .collect::<Vec<_>>();
+        Ok(lines.join("\n"))
+    }
+
+    pub fn undef(&mut self, name: &str) -> Result<(), String> {
+        self.runtime.undefine(&name.to_string())?;
+        self.source.retain(|(defined, _)| defined != name);
+        Ok(())
+    }
+
+    // drop all definitions. natives registered by the embedder are removed as well.
+    pub fn reset(&mut self) {
+        self.runtime = Runtime::new();
+        self.source.clear();
+        if self.stdlib {
+            self.load_stdlib();
+        }
+    }
+
+    pub fn load_file(&mut self, path: &str) -> ReplResult {
+        let mut content = String::new();
+        File::open(path)
+            .and_then(|mut file| file.read_to_string(&mut content))
+            .map_err(|err| format!("could not read `{}`: {}", path, err))?;
+        self.load(&content)
+    }
+
+    // write all lines that defined something in this session to `path`
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let mut content = String::new();
+        for (_, line) in self.source.iter() {
+            content.push_str(line);
+            content.push('\n');
+        }
+        File::create(path)
+            .and_then(|mut file| file.write_all(content.as_bytes()))
+            .map_err(|err| format!("could not write `{}`: {}", path, err))
+    }
+
+    pub fn time(&mut self, raw: &str) -> Result<(Option<lovm::Value>, Duration), String> {
+        let start = Instant::now();
+        let result = self.run(raw)?;
+        Ok((result, start.elapsed()))
+    }
+
+    // listing of the code generated for `raw`. the name of a function shows its dispatch.
+    pub fn bytecode(&self, raw: &str) -> Result<String, String> {
+        let program = self.parser.parse(raw).map_err(|err| format!("{:?}", err))?;
+        if let Expr::Ref(name) = &program {
+            if let Some(listing) = self.runtime.disassemble(name) {
+                return Ok(listing);
+            }
+        }
+        let co = self.runtime.compile(&program)?;
+        Ok(disasm::disassemble(&co))
+    }
+}
@@ -0,0 +1,179 @@
+use super::*;
+
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::{Context, Editor, Helper};
+
+use std::io::BufRead;
+use std::path::PathBuf;
+
+// interactive input for the repl. lines are read through a line editor with history and
+// completion if stdin is a terminal; otherwise they are read from stdin as they are. in both
+// modes an input continues on the next line as long as its brackets are unbalanced.
+
+const PROMPT: &str = "> ";
+const CONTINUE_PROMPT: &str = ". ";
+
+pub use localc_cc_lib::formatter::is_incomplete;
+
+use localc_cc_lib::formatter::{is_ident_char, split_comment};
+
+// file the history is stored in: `~/.localc_history`
+fn history_file() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".localc_history"))
+}
+
+// completes the identifier in front of the cursor with names known to the runtime
+#[derive(Default)]
+pub struct NameCompleter {
+    pub names: Vec<String>,
+}
+
+impl Completer for NameCompleter {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let start = line[..pos]
+            .rfind(|c: char| !is_ident_char(c))
+            .map_or(0, |idx| idx + 1);
+        let prefix = &line[start..pos];
+        let candidates = self
+            .names
+            .iter()
+            .filter(|name| !prefix.is_empty() && name.starts_with(prefix))
+            .cloned()
+            .collect();
+        Ok((start, candidates))
+    }
+}
+
+impl Hinter for NameCompleter {}
+
+impl Highlighter for NameCompleter {}
+
+impl Helper for NameCompleter {}
+
+impl Repl {
+    // names of all functions and globals
+    pub fn completions(&self) -> Vec<String> {
+        let mut names = self
+            .runtime
+            .functions()
+            .into_iter()
+            .cloned()
+            .collect::<Vec<_>>();
+        names.extend(
+            self.runtime
+                .globals()
+                .into_iter()
+                .map(|(name, _)| name.clone()),
+        );
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    pub fn repeat(&mut self) -> ReplResult {
+        if atty::is(atty::Stream::Stdin) {
+            self.repeat_interactive();
+        } else {
+            self.repeat_plain();
+        }
+        Ok(None)
+    }
+
+    fn repeat_interactive(&mut self) {
+        let mut editor = Editor::<NameCompleter>::new();
+        editor.set_helper(Some(NameCompleter::default()));
+        let history = history_file();
+        if let Some(history) = history.as_ref() {
+            // there is no history on first start
+            let _ = editor.load_history(history);
+        }
+
+        let mut input = String::new();
+        loop {
+            editor.helper_mut().unwrap().names = self.completions();
+            let prompt = if input.is_empty() {
+                PROMPT
+            } else {
+                CONTINUE_PROMPT
+            };
+            match editor.readline(prompt) {
+                Ok(line) => {
+                    push_line(&mut input, &line);
+                    if is_incomplete(&input) {
+                        continue;
+                    }
+                    editor.add_history_entry(input.as_str());
+                    self.interact(&input);
+                    input.clear();
+                }
+                // ctrl-c discards the current input
+                Err(ReadlineError::Interrupted) => input.clear(),
+                Err(ReadlineError::Eof) => break,
+                Err(err) => {
+                    println!("{}", err);
+                    break;
+                }
+            }
+        }
+
+        if let Some(history) = history.as_ref() {
+            if let Err(err) = editor.save_history(history) {
+                println!("could not save history: {}", err);
+            }
+        }
+    }
+
+    fn repeat_plain(&mut self) {
+        let mut input = String::new();
+        for line in std::io::stdin().lock().lines() {
+            push_line(&mut input, &line.unwrap());
+            if is_incomplete(&input) {
+                continue;
+            }
+            self.interact(&input);
+            input.clear();
+        }
+        if !input.trim().is_empty() {
+            self.interact(&input);
+        }
+    }
+
+    // run a line entered by the user and print the result
+    fn interact(&mut self, script: &str) {
+        if script.trim().is_empty() {
+            return;
+        }
+        if command::is_command(script) {
+            match self.command(script) {
+                Ok(output) if output.is_empty() => {}
+                Ok(output) => println!("{}", output),
+                Err(err) => println!("{}", err),
+            }
+            return;
+        }
+        match self.run(script) {
+            Ok(Some(result)) => println!("{}", self.format(&result)),
+            Ok(None) => {}
+            Err(err) => println!("{}", err),
+        }
+    }
+}
+
+// append `line` without its comment to `input`
+pub(crate) fn push_line(input: &mut String, line: &str) {
+    let (code, _) = split_comment(line);
+    if !input.is_empty() {
+        input.push(' ');
+    }
+    input.push_str(code);
+}
@@ -0,0 +1,101 @@
+pub mod command;
+pub mod editor;
+pub mod script;
+
+use super::*;
+
+pub type ReplResult = Result<Option<lovm::Value>, String>;
+
+// include content of stdlib here
+const STDLIB: &str = include_str!("../lclib/stdlib.lc");
+
+pub struct Repl {
+    pub parser: ExprParser,
+    pub runtime: Runtime,
+    // formatting of results
+    pub pretty: Pretty,
+    // source lines that changed the session, tagged with the name they define
+    source: Vec<(Name, String)>,
+    stdlib: bool,
+}
+
+impl Repl {
+    pub fn new() -> Self {
+        Self {
+            parser: ExprParser::new(),
+            runtime: Runtime::new(),
+            pretty: Pretty::new(),
+            source: vec![],
+            stdlib: false,
+        }
+    }
+
+    pub fn with_stdlib() -> Self {
+        let mut repl = Self::new();
+        repl.load_stdlib();
+        repl
+    }
+
+    fn load_stdlib(&mut self) {
+        self.load(STDLIB).expect("invalid code in stdlib");
+        // the stdlib is not part of the users session
+        self.source.clear();
+        self.stdlib = true;
+    }
+
+    // run every line of `src`. function definitions are only compiled once before the next
+    // expression is evaluated or the source has been loaded completely.
+    pub fn load(&mut self, src: &str) -> ReplResult {
+        let mut result = None;
+        for line in src.lines().filter(|line| !line.trim().is_empty()) {
+            result = self.run(line)?;
+        }
+        self.runtime.sync()?;
+        Ok(result)
+    }
+
+    pub fn run(&mut self, raw: &str) -> ReplResult {
+        let (raw, _) = localc_cc_lib::formatter::split_comment(raw);
+        if raw.trim().is_empty() {
+            return Ok(None);
+        }
+        let program = self.parser.parse(raw).map_err(|err| format!("{:?}", err))?;
+        self.run_parsed(raw, &program)
+    }
+
+    // run `program` that was parsed from `raw`
+    pub fn run_parsed(&mut self, raw: &str, program: &Expr) -> ReplResult {
+        let result = self.run_expr(program)?;
+        if let Some(name) = command::defined_name(program) {
+            self.source.push((name, raw.to_string()));
+        }
+        Ok(result)
+    }
+
+    pub fn run_expr(&mut self, expr: &Expr) -> ReplResult {
+        self.runtime.run_expr(expr)
+    }
+
+    // like `run`, but the result is returned as owned value
+    pub fn eval(&mut self, raw: &str) -> Result<Option<ast::Value>, String> {
+        match self.run(raw)? {
+            Some(value) => self.runtime.to_value(&value).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    // render a result in localc syntax
+    pub fn format(&self, value: &lovm::Value) -> String {
+        self.runtime.pretty(value, &self.pretty)
+    }
+
+    // assign `value` to the global `name`
+    pub fn set<T>(&mut self, name: &str, value: T) -> Result<(), String>
+    where
+        T: Into<ast::Value>,
+    {
+        let value = Expr::Value(value.into());
+        self.runtime.store_var(&name.to_string(), &value)?;
+        Ok(())
+    }
+}
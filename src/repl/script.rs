@@ -0,0 +1,234 @@
+use super::*;
+
+use localc_cc_lib::formatter::split_comment;
+use serde_json::json;
+
+use std::fs::File;
+use std::io::Read;
+
+// non-interactive execution of localc files. statements span multiple lines while their
+// brackets are unbalanced; comments are ignored. errors point to the line and column of the
+// failing statement.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ErrorKind {
+    Parse,
+    Runtime,
+}
+
+#[derive(Clone, Debug)]
+pub struct ScriptError {
+    pub kind: ErrorKind,
+    pub message: String,
+    pub file: String,
+    // both starting at 1
+    pub line: usize,
+    pub col: usize,
+    // position behind the erroneous code
+    pub end: (usize, usize),
+    pub statement: String,
+}
+
+impl ScriptError {
+    // object with kind, message and span for machine readable output
+    pub fn to_json(&self) -> serde_json::Value {
+        let kind = match self.kind {
+            ErrorKind::Parse => "parse",
+            ErrorKind::Runtime => "runtime",
+        };
+        json!({
+            "kind": kind,
+            "message": self.message,
+            "file": self.file,
+            "statement": self.statement,
+            "span": {
+                "start": {"line": self.line, "col": self.col},
+                "end": {"line": self.end.0, "col": self.end.1},
+            },
+        })
+    }
+}
+
+impl std::fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        let kind = match self.kind {
+            ErrorKind::Parse => "parse error",
+            ErrorKind::Runtime => "error",
+        };
+        writeln!(
+            f,
+            "{}:{}:{}: {}: {}",
+            self.file, self.line, self.col, kind, self.message
+        )?;
+        write!(f, "    {}", self.statement)
+    }
+}
+
+// a statement and the position of each of its source lines inside of it
+pub(crate) struct Statement {
+    pub src: String,
+    // line number and offset into `src` for every line
+    pub lines: Vec<(usize, usize)>,
+}
+
+impl Statement {
+    // translate an offset into `src` to line and column
+    pub fn position(&self, offset: usize) -> (usize, usize) {
+        let (line, start) = self
+            .lines
+            .iter()
+            .rev()
+            .find(|(_, start)| *start <= offset)
+            .cloned()
+            .unwrap_or((self.lines[0].0, 0));
+        (line, offset - start + 1)
+    }
+
+    // position of the first character that is not whitespace
+    pub fn start(&self) -> (usize, usize) {
+        let offset = self.src.len() - self.src.trim_start().len();
+        self.position(offset)
+    }
+
+    // position behind the last character that is not whitespace
+    pub fn end(&self) -> (usize, usize) {
+        self.position(self.src.trim_end().len())
+    }
+}
+
+pub(crate) fn statements(src: &str) -> Vec<Statement> {
+    let mut statements = vec![];
+    let mut current: Option<Statement> = None;
+
+    for (i, line) in src.lines().enumerate() {
+        if current.is_none() && split_comment(line).0.trim().is_empty() {
+            continue;
+        }
+        let stmt = current.get_or_insert_with(|| Statement {
+            src: String::new(),
+            lines: vec![],
+        });
+        let offset = if stmt.src.is_empty() {
+            0
+        } else {
+            stmt.src.len() + 1
+        };
+        stmt.lines.push((i + 1, offset));
+        editor::push_line(&mut stmt.src, line);
+        if !editor::is_incomplete(&stmt.src) {
+            statements.push(current.take().unwrap());
+        }
+    }
+
+    // unbalanced brackets at the end of the file are reported by the parser
+    statements.extend(current);
+    statements
+}
+
+impl Repl {
+    // run all statements of `src`. `file` is only used for reporting errors. if `keep_going`
+    // is set, statements after a failing one are still executed.
+    pub fn run_script(&mut self, file: &str, src: &str, keep_going: bool) -> Vec<ScriptError> {
+        self.run_script_with(file, src, keep_going, |_, _| {})
+    }
+
+    // like `run_script`, but `on_result` is called with every result of a statement
+    pub fn run_script_with<F>(
+        &mut self,
+        file: &str,
+        src: &str,
+        keep_going: bool,
+        mut on_result: F,
+    ) -> Vec<ScriptError>
+    where
+        F: FnMut(&Self, &lovm::Value),
+    {
+        let mut errors = vec![];
+
+        for stmt in statements(src).iter() {
+            match self.run_statement(file, stmt) {
+                Ok(Some(result)) => on_result(self, &result),
+                Ok(None) => {}
+                Err(err) => {
+                    errors.push(err);
+                    if !keep_going {
+                        return errors;
+                    }
+                }
+            }
+        }
+
+        if let Err(message) = self.runtime.sync() {
+            let line = src.lines().count();
+            errors.push(ScriptError {
+                kind: ErrorKind::Runtime,
+                message,
+                file: file.to_string(),
+                line,
+                col: 1,
+                end: (line, 1),
+                statement: String::new(),
+            });
+        }
+
+        errors
+    }
+
+    // parse and run a single statement of the file `file`
+    pub(crate) fn run_statement(
+        &mut self,
+        file: &str,
+        stmt: &Statement,
+    ) -> Result<Option<lovm::Value>, ScriptError> {
+        let error = |kind, message, (line, col), end| ScriptError {
+            kind,
+            message,
+            file: file.to_string(),
+            line,
+            col,
+            end,
+            statement: stmt.src.trim().to_string(),
+        };
+
+        match self.parser.parse(&stmt.src) {
+            Ok(program) => self
+                .run_parsed(&stmt.src, &program)
+                .map_err(|message| error(ErrorKind::Runtime, message, stmt.start(), stmt.end())),
+            Err(err) => {
+                let offset = localc_cc_lib::error_offset(&err).unwrap_or(stmt.src.len());
+                let message = format!("{:?}", err);
+                let end = stmt.position(offset + 1);
+                Err(error(ErrorKind::Parse, message, stmt.position(offset), end))
+            }
+        }
+    }
+
+    pub fn run_file(&mut self, path: &str, keep_going: bool) -> Result<Vec<ScriptError>, String> {
+        self.run_file_with(path, keep_going, |_, _| {})
+    }
+
+    pub fn run_file_with<F>(
+        &mut self,
+        path: &str,
+        keep_going: bool,
+        on_result: F,
+    ) -> Result<Vec<ScriptError>, String>
+    where
+        F: FnMut(&Self, &lovm::Value),
+    {
+        // `-` reads the script from stdin
+        let src = localc_cc_lib::compiler::read_source(path)?;
+        Ok(self.run_script_with(path, &src, keep_going, on_result))
+    }
+
+    // load a unit compiled by `localc-cc`
+    pub fn load_unit_file(&mut self, path: &str) -> Result<(), String> {
+        let mut buffer = vec![];
+        File::open(path)
+            .and_then(|mut file| file.read_to_end(&mut buffer))
+            .map_err(|err| format!("could not read `{}`: {}", path, err))?;
+        let unit = lovm::Unit::deserialize(&buffer)
+            .map_err(|err| format!("invalid unit `{}`: {:?}", path, err))?;
+        self.runtime.load_unit(unit)
+    }
+}
@@ -0,0 +1,21 @@
+use super::*;
+
+// functions every script relies on
+
+pub(super) const FUNCTIONS: &[(&str, usize, Builtin)] =
+    &[("assert", 1, assert), ("assert", 2, assert)];
+
+// fails the execution if the first argument is false. the optional second argument is
+// reported as message.
+fn assert(_: &mut vm::VmData, args: &[lovm::Value]) -> NativeResult {
+    match args {
+        [lovm::Value::T(true)] | [lovm::Value::T(true), _] => Ok(lovm::Value::T(true)),
+        [lovm::Value::T(false)] => Err("assertion failed".to_string()),
+        [lovm::Value::T(false), lovm::Value::Str(message)] => {
+            Err(format!("assertion failed: {}", message))
+        }
+        [lovm::Value::T(false), message] => Err(format!("assertion failed: {:?}", message)),
+        [other, ..] => Err(format!("assert expects a logical value, got `{:?}`", other)),
+        [] => Err("assert expects a logical value".to_string()),
+    }
+}
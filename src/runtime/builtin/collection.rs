@@ -0,0 +1,398 @@
+use super::*;
+
+use localc_cc_lib::ast::Value;
+
+use std::cmp::Ordering;
+
+// functions over tuples and sets. sets are seen as the tuple of their values in the order of
+// their keys. natives cannot call back into localc code, so `map(f, xs)`, `filter(f, xs)` and
+// `fold(f, init, xs)` are lowered into calls of a helper generated for every callback `f`.
+// helpers count down the remaining items and only make self tail calls, which are executed
+// as a loop.
+
+// longest tuple `range` creates
+const MAX_RANGE: usize = 10_000_000;
+
+pub(super) const FUNCTIONS: &[(&str, usize, Builtin)] = &[
+    ("len", 1, len),
+    ("range", 2, range),
+    ("range", 3, range),
+    ("zip", 2, zip),
+    ("sort", 1, sort),
+    ("reverse", 1, reverse),
+    ("unique", 1, unique),
+    ("contains", 2, contains),
+    ("keys", 1, keys),
+    ("values", 1, values),
+    ("concat", 2, concat),
+    ("take", 2, take),
+    ("drop", 2, skip),
+    // used by the helpers. `@` cannot be part of names in localc code.
+    ("@items", 1, items),
+    ("@item", 2, item),
+    ("@push", 2, push),
+    ("@keep", 3, keep),
+];
+
+// functions taking a callback as first argument and their number of arguments
+pub const CALLBACKS: &[(&str, usize)] = &[("map", 2), ("filter", 2), ("fold", 3)];
+
+// true for names of generated helpers and the natives they use
+pub fn is_hidden(name: &str) -> bool {
+    name.contains('@')
+}
+
+pub fn helper_name(kind: &str, callback: &str) -> Name {
+    format!("{}@{}", kind, callback)
+}
+
+fn call(name: &str, args: Vec<Expr>) -> Expr {
+    Expr::Func(name.to_string(), args)
+}
+
+fn var(name: &str) -> Expr {
+    Expr::Ref(name.to_string())
+}
+
+fn int(n: i64) -> Expr {
+    Expr::Value(Value::Numeric(NumType::Natural(n)))
+}
+
+fn comp(op: Operator, lhs: Expr, rhs: Expr) -> Expr {
+    Expr::Comp(op, Box::new(lhs), Box::new(rhs))
+}
+
+// rewrite `map(f, xs)` into `map@f(xs)`, `filter(f, xs)` into `filter@f(xs)` and
+// `fold(f, init, xs)` into `fold@f(init, xs)`. kinds and callbacks of the rewritten calls are
+// added to `found`.
+pub fn lower(expr: &Expr, found: &mut Vec<(String, Name)>) -> Expr {
+    match expr {
+        Expr::Comp(op, lhs, rhs) => comp(op.clone(), lower(lhs, found), lower(rhs, found)),
+        Expr::Func(name, args) => {
+            let args = args.iter().map(|arg| lower(arg, found)).collect::<Vec<_>>();
+            let is_callback = CALLBACKS
+                .iter()
+                .any(|(kind, argc)| *kind == name.as_str() && *argc == args.len());
+            match args.split_first() {
+                Some((Expr::Ref(callback), rest)) if is_callback => {
+                    let helper = helper_name(name, callback);
+                    found.push((name.clone(), callback.clone()));
+                    Expr::Func(helper, rest.to_vec())
+                }
+                _ => Expr::Func(name.clone(), args),
+            }
+        }
+        Expr::Value(Value::Tuple(items)) => Expr::Value(Value::Tuple(
+            items.iter().map(|item| lower(item, found)).collect(),
+        )),
+        Expr::Value(Value::Set(entries)) => Expr::Value(Value::Set(
+            entries
+                .iter()
+                .map(|(key, value)| {
+                    let key = key.as_ref().map(|key| lower(key, found));
+                    (key, lower(value, found))
+                })
+                .collect(),
+        )),
+        other => other.clone(),
+    }
+}
+
+// overloads of the helper that applies `kind` with `callback`:
+//
+//  map@f(xs) = map@f(len(xs), @items(xs), 0, ())
+//  map@f(0, xs, i, acc) = acc
+//  map@f(n, xs, i, acc) = map@f(n - 1, xs, i + 1, @push(acc, f(@item(xs, i))))
+pub fn helper(kind: &str, callback: &str) -> Vec<(TupleType, Expr)> {
+    let name = helper_name(kind, callback);
+    let item = call("@item", vec![var("xs"), var("i")]);
+    let (entry, init, next) = match kind {
+        "map" => (
+            vec![var("xs")],
+            Expr::Value(Value::Tuple(vec![])),
+            call(
+                "@push",
+                vec![var("acc"), call(callback, vec![item.clone()])],
+            ),
+        ),
+        "filter" => (
+            vec![var("xs")],
+            Expr::Value(Value::Tuple(vec![])),
+            call(
+                "@keep",
+                vec![var("acc"), item.clone(), call(callback, vec![item.clone()])],
+            ),
+        ),
+        _ => (
+            vec![var("init"), var("xs")],
+            var("init"),
+            call(callback, vec![var("acc"), item.clone()]),
+        ),
+    };
+
+    let start = call(
+        &name,
+        vec![
+            call("len", vec![var("xs")]),
+            call("@items", vec![var("xs")]),
+            int(0),
+            init,
+        ],
+    );
+    let step = call(
+        &name,
+        vec![
+            comp(Operator::Sub, var("n"), int(1)),
+            var("xs"),
+            comp(Operator::Add, var("i"), int(1)),
+            next,
+        ],
+    );
+
+    vec![
+        (entry, start),
+        (vec![int(0), var("xs"), var("i"), var("acc")], var("acc")),
+        (vec![var("n"), var("xs"), var("i"), var("acc")], step),
+    ]
+}
+
+// order of values: logical values before numbers before strings before tuples. tuples are
+// compared item by item.
+fn compare(data: &vm::VmData, a: &lovm::Value, b: &lovm::Value) -> Ordering {
+    fn rank(value: &lovm::Value) -> usize {
+        match value {
+            lovm::Value::T(_) => 0,
+            lovm::Value::I64(_) | lovm::Value::F64(_) => 1,
+            lovm::Value::Str(_) => 2,
+            _ => 3,
+        }
+    }
+
+    match (a, b) {
+        (lovm::Value::T(a), lovm::Value::T(b)) => a.cmp(b),
+        (lovm::Value::Str(a), lovm::Value::Str(b)) => a.to_string().cmp(&b.to_string()),
+        (lovm::Value::Ref(_), lovm::Value::Ref(_)) => {
+            match (native::array(data, a), native::array(data, b)) {
+                (Ok(a), Ok(b)) => a
+                    .iter()
+                    .zip(b.iter())
+                    .map(|(a, b)| compare(data, a, b))
+                    .find(|order| *order != Ordering::Equal)
+                    .unwrap_or_else(|| a.len().cmp(&b.len())),
+                _ => Ordering::Equal,
+            }
+        }
+        _ => match (native::number(a), native::number(b)) {
+            (Ok(a), Ok(b)) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+            _ => rank(a).cmp(&rank(b)),
+        },
+    }
+}
+
+// numbers are equal if their values are, tuples if their items are
+fn equal(data: &vm::VmData, a: &lovm::Value, b: &lovm::Value) -> bool {
+    match (a, b) {
+        (lovm::Value::Ref(_), lovm::Value::Ref(_)) => {
+            match (native::array(data, a), native::array(data, b)) {
+                (Ok(a), Ok(b)) => {
+                    a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| equal(data, a, b))
+                }
+                _ => a == b,
+            }
+        }
+        _ => match (native::number(a), native::number(b)) {
+            (Ok(a), Ok(b)) => a == b,
+            _ => a == b,
+        },
+    }
+}
+
+// entries of a set in the order of their keys
+fn entries(
+    data: &vm::VmData,
+    value: &lovm::Value,
+) -> Result<Vec<(lovm::Value, lovm::Value)>, String> {
+    let mut entries = native::dict(data, value)?.into_iter().collect::<Vec<_>>();
+    entries.sort_by(|(a, _), (b, _)| compare(data, a, b));
+    Ok(entries)
+}
+
+// items of a tuple or values of a set
+fn elements(data: &vm::VmData, value: &lovm::Value) -> Result<Vec<lovm::Value>, String> {
+    if let Ok(entries) = entries(data, value) {
+        return Ok(entries.into_iter().map(|(_, value)| value).collect());
+    }
+    native::array(data, value).map_err(|_| format!("expected tuple or set, got `{:?}`", value))
+}
+
+fn count(value: &lovm::Value) -> Result<usize, String> {
+    match value {
+        lovm::Value::I64(n) if 0 <= *n => Ok(*n as usize),
+        other => Err(format!("expected count, got `{:?}`", other)),
+    }
+}
+
+// number of items of a tuple, entries of a set or characters of a string
+fn len(data: &mut vm::VmData, args: &[lovm::Value]) -> NativeResult {
+    let len = match &args[0] {
+        lovm::Value::Str(s) => s.to_string().chars().count(),
+        other => elements(data, other)?.len(),
+    };
+    Ok(lovm::Value::I64(len as i64))
+}
+
+// numbers from `a` up to but excluding `b` in steps of `step`, which defaults to 1. the
+// numbers are integers if all arguments are.
+fn range(data: &mut vm::VmData, args: &[lovm::Value]) -> NativeResult {
+    let step = args.get(2).cloned().unwrap_or(lovm::Value::I64(1));
+    let (a, b, s) = (
+        native::number(&args[0])?,
+        native::number(&args[1])?,
+        native::number(&step)?,
+    );
+    if s == 0. {
+        return Err("step of range must not be zero".to_string());
+    }
+    let len = ((b - a) / s).ceil().max(0.);
+    if MAX_RANGE as f64 <= len {
+        return Err(format!("range is longer than {} items", MAX_RANGE));
+    }
+
+    let items = match (&args[0], &args[1], &step) {
+        (lovm::Value::I64(a), lovm::Value::I64(_), lovm::Value::I64(s)) => (0..len as i64)
+            .map(|i| lovm::Value::I64(a + i * s))
+            .collect(),
+        _ => (0..len as i64)
+            .map(|i| lovm::Value::F64(a + i as f64 * s))
+            .collect(),
+    };
+    Ok(native::alloc_array(data, items))
+}
+
+// pairs of items at the same position. the longer tuple is cut off.
+fn zip(data: &mut vm::VmData, args: &[lovm::Value]) -> NativeResult {
+    let (a, b) = (elements(data, &args[0])?, elements(data, &args[1])?);
+    let pairs = a
+        .into_iter()
+        .zip(b.into_iter())
+        .map(|(a, b)| native::alloc_array(data, vec![a, b]))
+        .collect();
+    Ok(native::alloc_array(data, pairs))
+}
+
+fn sort(data: &mut vm::VmData, args: &[lovm::Value]) -> NativeResult {
+    let mut items = elements(data, &args[0])?;
+    items.sort_by(|a, b| compare(data, a, b));
+    Ok(native::alloc_array(data, items))
+}
+
+fn reverse(data: &mut vm::VmData, args: &[lovm::Value]) -> NativeResult {
+    let mut items = elements(data, &args[0])?;
+    items.reverse();
+    Ok(native::alloc_array(data, items))
+}
+
+// items without repetitions in the order of their first occurrence
+fn unique(data: &mut vm::VmData, args: &[lovm::Value]) -> NativeResult {
+    let mut items: Vec<lovm::Value> = vec![];
+    for item in elements(data, &args[0])?.into_iter() {
+        if !items.iter().any(|other| equal(data, other, &item)) {
+            items.push(item);
+        }
+    }
+    Ok(native::alloc_array(data, items))
+}
+
+// `contains(xs, x)` tests the items of a tuple, the keys of a set or the substrings of a string
+fn contains(data: &mut vm::VmData, args: &[lovm::Value]) -> NativeResult {
+    let found = match (&args[0], &args[1]) {
+        (lovm::Value::Str(s), lovm::Value::Str(sub)) => s.to_string().contains(&sub.to_string()),
+        (value, x) => match entries(data, value) {
+            Ok(entries) => entries.iter().any(|(key, _)| equal(data, key, x)),
+            _ => elements(data, value)?
+                .iter()
+                .any(|item| equal(data, item, x)),
+        },
+    };
+    Ok(lovm::Value::T(found))
+}
+
+fn keys(data: &mut vm::VmData, args: &[lovm::Value]) -> NativeResult {
+    let keys = entries(data, &args[0])?
+        .into_iter()
+        .map(|(key, _)| key)
+        .collect();
+    Ok(native::alloc_array(data, keys))
+}
+
+fn values(data: &mut vm::VmData, args: &[lovm::Value]) -> NativeResult {
+    let values = entries(data, &args[0])?
+        .into_iter()
+        .map(|(_, value)| value)
+        .collect();
+    Ok(native::alloc_array(data, values))
+}
+
+// join two tuples or strings. joining sets keeps the value of the second set for keys that
+// are in both.
+fn concat(data: &mut vm::VmData, args: &[lovm::Value]) -> NativeResult {
+    if let (lovm::Value::Str(a), lovm::Value::Str(b)) = (&args[0], &args[1]) {
+        return Ok(lovm::Value::Str(format!("{}{}", a, b).as_str().into()));
+    }
+    if let (Ok(mut a), Ok(b)) = (native::dict(data, &args[0]), native::dict(data, &args[1])) {
+        a.extend(b.into_iter());
+        return Ok(native::alloc_dict(data, a.into_iter().collect()));
+    }
+    let mut items = native::array(data, &args[0])?;
+    items.extend(native::array(data, &args[1])?.into_iter());
+    Ok(native::alloc_array(data, items))
+}
+
+// the first `n` items of a tuple or values of a set
+fn take(data: &mut vm::VmData, args: &[lovm::Value]) -> NativeResult {
+    let mut items = elements(data, &args[0])?;
+    items.truncate(count(&args[1])?);
+    Ok(native::alloc_array(data, items))
+}
+
+// all but the first `n` items of a tuple or values of a set
+fn skip(data: &mut vm::VmData, args: &[lovm::Value]) -> NativeResult {
+    let items = elements(data, &args[0])?
+        .into_iter()
+        .skip(count(&args[1])?)
+        .collect();
+    Ok(native::alloc_array(data, items))
+}
+
+fn items(data: &mut vm::VmData, args: &[lovm::Value]) -> NativeResult {
+    let items = elements(data, &args[0])?;
+    Ok(native::alloc_array(data, items))
+}
+
+fn item(data: &mut vm::VmData, args: &[lovm::Value]) -> NativeResult {
+    let index = count(&args[1])?;
+    native::array_ref(data, &args[0])?
+        .get(index)
+        .cloned()
+        .ok_or_else(|| format!("no item at index {}", index))
+}
+
+// tuples are values, so the accumulator is copied instead of changed in place. other holders
+// of the same reference, like cached results of memoized functions, must not see the push.
+fn push(data: &mut vm::VmData, args: &[lovm::Value]) -> NativeResult {
+    let mut items = native::array_ref(data, &args[0])?.clone();
+    items.push(args[1].clone());
+    Ok(native::alloc_array(data, items))
+}
+
+// push `args[1]` if the callback of `filter` returned true for it
+fn keep(data: &mut vm::VmData, args: &[lovm::Value]) -> NativeResult {
+    match &args[2] {
+        lovm::Value::T(true) => push(data, args),
+        lovm::Value::T(false) => Ok(args[0].clone()),
+        other => Err(format!(
+            "filter expects a logical value from its callback, got `{:?}`",
+            other
+        )),
+    }
+}
@@ -0,0 +1,58 @@
+use super::*;
+
+use std::fs::File;
+use std::io::Read;
+
+// reading data from files
+
+pub(super) const EFFECTS: &[(&str, usize, Builtin)] = &[("json", 1, json)];
+
+// load a json document. arrays become tuples and objects become sets keyed by strings. json
+// has more types than lovm: documents containing `null` are rejected because lovm has no value
+// for it.
+fn json(data: &mut vm::VmData, args: &[lovm::Value]) -> NativeResult {
+    let path = match args {
+        [lovm::Value::Str(path)] => path.to_string(),
+        _ => return Err("usage: json(path)".to_string()),
+    };
+    let mut content = String::new();
+    File::open(&path)
+        .and_then(|mut file| file.read_to_string(&mut content))
+        .map_err(|err| format!("could not read `{}`: {}", path, err))?;
+    let document = serde_json::from_str::<serde_json::Value>(&content)
+        .map_err(|err| format!("invalid json in `{}`: {}", path, err))?;
+    load(data, &document).map_err(|err| format!("cannot load `{}`: {}", path, err))
+}
+
+fn load(data: &mut vm::VmData, value: &serde_json::Value) -> NativeResult {
+    use serde_json::Value::*;
+
+    let value = match value {
+        Null => return Err("`null` has no localc value".to_string()),
+        Bool(t) => lovm::Value::T(*t),
+        Number(n) => match n.as_i64() {
+            Some(n) => lovm::Value::I64(n),
+            // numbers outside of i64 are read as floats as well
+            _ => lovm::Value::F64(n.as_f64().unwrap_or(std::f64::NAN)),
+        },
+        String(s) => lovm::Value::Str(s.as_str().into()),
+        Array(items) => {
+            let items = items
+                .iter()
+                .map(|item| load(data, item))
+                .collect::<Result<_, _>>()?;
+            native::alloc_array(data, items)
+        }
+        Object(entries) => {
+            let entries = entries
+                .iter()
+                .map(|(key, value)| {
+                    let key = lovm::Value::Str(key.as_str().into());
+                    Ok((key, load(data, value)?))
+                })
+                .collect::<Result<_, String>>()?;
+            native::alloc_dict(data, entries)
+        }
+    };
+    Ok(value)
+}
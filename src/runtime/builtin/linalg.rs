@@ -0,0 +1,453 @@
+use super::*;
+
+use localc_cc_lib::ast::Value;
+
+// vectors are tuples of numbers, matrices are tuples of row tuples. integer operands stay
+// integers as long as the operation allows it; `det`, `inv` and `lsolve` always work on floats.
+
+// pivots smaller than this fraction of the largest entry are treated as zero
+const EPSILON: f64 = 1e-12;
+
+pub(super) const FUNCTIONS: &[(&str, usize, Builtin)] = &[
+    ("vadd", 2, vadd),
+    ("vsub", 2, vsub),
+    ("vmul", 2, vmul),
+    ("scale", 2, scale),
+    ("dot", 2, dot),
+    ("matmul", 2, matmul),
+    ("transpose", 1, transpose),
+    ("det", 1, det),
+    ("inv", 1, inv),
+    ("lsolve", 2, lsolve),
+    // targets of `+`, `-` and `*`, see `lower`. hidden so that redefining `vadd` does not
+    // change the operators.
+    ("@add", 2, vadd),
+    ("@sub", 2, vsub),
+    ("@mul", 2, vmul),
+];
+
+#[derive(Clone, Debug)]
+enum Tensor {
+    Scalar(lovm::Value),
+    Tuple(Vec<Tensor>),
+}
+
+impl Tensor {
+    fn load(data: &vm::VmData, value: &lovm::Value) -> Result<Self, String> {
+        match value {
+            lovm::Value::Ref(_) => {
+                let items = native::array(data, value)?
+                    .iter()
+                    .map(|item| Tensor::load(data, item))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(Tensor::Tuple(items))
+            }
+            _ => Ok(Tensor::Scalar(value.clone())),
+        }
+    }
+
+    fn store(self, data: &mut vm::VmData) -> lovm::Value {
+        match self {
+            Tensor::Scalar(value) => value,
+            Tensor::Tuple(items) => {
+                let items = items.into_iter().map(|item| item.store(data)).collect();
+                native::alloc_array(data, items)
+            }
+        }
+    }
+
+    fn zip_with<F>(self, other: Self, f: &F) -> Result<Self, String>
+    where
+        F: Fn(&lovm::Value, &lovm::Value) -> NativeResult,
+    {
+        match (self, other) {
+            (Tensor::Scalar(a), Tensor::Scalar(b)) => Ok(Tensor::Scalar(f(&a, &b)?)),
+            (Tensor::Tuple(a), Tensor::Tuple(b)) => {
+                if a.len() != b.len() {
+                    return Err(format!(
+                        "tuple dimensions do not match ({} and {})",
+                        a.len(),
+                        b.len()
+                    ));
+                }
+                let items = a
+                    .into_iter()
+                    .zip(b.into_iter())
+                    .map(|(a, b)| a.zip_with(b, f))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(Tensor::Tuple(items))
+            }
+            _ => Err("cannot combine scalar and tuple element-wise".to_string()),
+        }
+    }
+
+    fn map<F>(self, f: &F) -> Result<Self, String>
+    where
+        F: Fn(&lovm::Value) -> NativeResult,
+    {
+        match self {
+            Tensor::Scalar(a) => Ok(Tensor::Scalar(f(&a)?)),
+            Tensor::Tuple(items) => {
+                let items = items
+                    .into_iter()
+                    .map(|item| item.map(f))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(Tensor::Tuple(items))
+            }
+        }
+    }
+
+    fn is_matrix(&self) -> bool {
+        match self {
+            Tensor::Tuple(rows) => rows.iter().all(|row| match row {
+                Tensor::Tuple(_) => true,
+                _ => false,
+            }),
+            _ => false,
+        }
+    }
+
+    fn into_vector(self) -> Result<Vec<lovm::Value>, String> {
+        match self {
+            Tensor::Tuple(items) => items
+                .into_iter()
+                .map(|item| match item {
+                    Tensor::Scalar(value) => Ok(value),
+                    _ => Err("expected vector, got matrix".to_string()),
+                })
+                .collect(),
+            _ => Err("expected vector, got scalar".to_string()),
+        }
+    }
+
+    fn into_matrix(self) -> Result<Vec<Vec<lovm::Value>>, String> {
+        let rows = match self {
+            Tensor::Tuple(rows) => rows
+                .into_iter()
+                .map(Tensor::into_vector)
+                .collect::<Result<Vec<_>, _>>()?,
+            _ => return Err("expected matrix, got scalar".to_string()),
+        };
+        let cols = rows.first().map_or(0, Vec::len);
+        if rows.iter().any(|row| row.len() != cols) {
+            return Err("matrix rows must have equal length".to_string());
+        }
+        Ok(rows)
+    }
+
+    fn from_vector(items: Vec<lovm::Value>) -> Self {
+        Tensor::Tuple(items.into_iter().map(Tensor::Scalar).collect())
+    }
+
+    fn from_matrix(rows: Vec<Vec<lovm::Value>>) -> Self {
+        Tensor::Tuple(rows.into_iter().map(Tensor::from_vector).collect())
+    }
+}
+
+fn add(a: &lovm::Value, b: &lovm::Value) -> NativeResult {
+    match (a, b) {
+        (lovm::Value::Str(a), lovm::Value::Str(b)) => {
+            Ok(lovm::Value::Str(format!("{}{}", a, b).as_str().into()))
+        }
+        (lovm::Value::I64(a), lovm::Value::I64(b)) => a
+            .checked_add(*b)
+            .map(lovm::Value::I64)
+            .ok_or_else(|| "integer overflow".to_string()),
+        _ => Ok(lovm::Value::F64(native::number(a)? + native::number(b)?)),
+    }
+}
+
+fn sub(a: &lovm::Value, b: &lovm::Value) -> NativeResult {
+    match (a, b) {
+        (lovm::Value::I64(a), lovm::Value::I64(b)) => a
+            .checked_sub(*b)
+            .map(lovm::Value::I64)
+            .ok_or_else(|| "integer overflow".to_string()),
+        _ => Ok(lovm::Value::F64(native::number(a)? - native::number(b)?)),
+    }
+}
+
+fn mul(a: &lovm::Value, b: &lovm::Value) -> NativeResult {
+    match (a, b) {
+        (lovm::Value::I64(a), lovm::Value::I64(b)) => a
+            .checked_mul(*b)
+            .map(lovm::Value::I64)
+            .ok_or_else(|| "integer overflow".to_string()),
+        _ => Ok(lovm::Value::F64(native::number(a)? * native::number(b)?)),
+    }
+}
+
+fn dot_product(a: &[lovm::Value], b: &[lovm::Value]) -> NativeResult {
+    if a.len() != b.len() {
+        return Err(format!(
+            "vector dimensions do not match ({} and {})",
+            a.len(),
+            b.len()
+        ));
+    }
+    a.iter()
+        .zip(b.iter())
+        .try_fold(lovm::Value::I64(0), |acc, (a, b)| add(&acc, &mul(a, b)?))
+}
+
+fn matrix_product(a: Vec<Vec<lovm::Value>>, b: Vec<Vec<lovm::Value>>) -> Result<Tensor, String> {
+    let inner = b.len();
+    if a.first().map_or(0, Vec::len) != inner {
+        return Err(format!(
+            "cannot multiply {}x{} and {}x{} matrices",
+            a.len(),
+            a.first().map_or(0, Vec::len),
+            inner,
+            b.first().map_or(0, Vec::len),
+        ));
+    }
+    let b = transpose_rows(b);
+    let rows = a
+        .iter()
+        .map(|row| b.iter().map(|col| dot_product(row, col)).collect())
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Tensor::from_matrix(rows))
+}
+
+fn transpose_rows<T: Clone>(rows: Vec<Vec<T>>) -> Vec<Vec<T>> {
+    let cols = rows.first().map_or(0, Vec::len);
+    (0..cols)
+        .map(|c| rows.iter().map(|row| row[c].clone()).collect())
+        .collect()
+}
+
+fn float_matrix(rows: Vec<Vec<lovm::Value>>) -> Result<Vec<Vec<f64>>, String> {
+    rows.iter()
+        .map(|row| row.iter().map(native::number).collect())
+        .collect()
+}
+
+fn square(rows: &[Vec<f64>]) -> Result<usize, String> {
+    let n = rows.len();
+    if rows.iter().any(|row| row.len() != n) {
+        return Err(format!(
+            "expected square matrix, got {}x{}",
+            n,
+            rows.first().map_or(0, Vec::len)
+        ));
+    }
+    Ok(n)
+}
+
+// bring `m` into row echelon form using partial pivoting. returns the determinant factor
+// produced by row swaps and pivots, or `None` if the matrix is singular. the tolerance for
+// pivots is relative to the largest entry of the first `n` columns, so matrices with small
+// entries are not mistaken for singular ones.
+fn eliminate(m: &mut Vec<Vec<f64>>, n: usize) -> Option<f64> {
+    let norm = m
+        .iter()
+        .flat_map(|row| row.iter().take(n))
+        .fold(0., |max: f64, x| max.max(x.abs()));
+    let tolerance = EPSILON * norm;
+    let mut factor = 1.;
+    for col in 0..n {
+        let pivot = (col..n).max_by(|a, b| {
+            m[*a][col]
+                .abs()
+                .partial_cmp(&m[*b][col].abs())
+                .unwrap_or(std::cmp::Ordering::Equal)
+        })?;
+        if m[pivot][col].abs() <= tolerance {
+            return None;
+        }
+        if pivot != col {
+            m.swap(pivot, col);
+            factor = -factor;
+        }
+        factor *= m[col][col];
+        for row in col + 1..n {
+            let ratio = m[row][col] / m[col][col];
+            for k in col..m[row].len() {
+                m[row][k] -= ratio * m[col][k];
+            }
+        }
+    }
+    Some(factor)
+}
+
+// solve `a x = b` for every column of `b` using gaussian elimination
+fn solve_columns(a: Vec<Vec<f64>>, b: Vec<Vec<f64>>) -> Result<Vec<Vec<f64>>, String> {
+    let n = square(&a)?;
+    if b.len() != n {
+        return Err(format!(
+            "right-hand side has {} rows, expected {}",
+            b.len(),
+            n
+        ));
+    }
+    let width = b.first().map_or(0, Vec::len);
+    let mut m = a
+        .into_iter()
+        .zip(b.into_iter())
+        .map(|(mut row, rhs)| {
+            row.extend(rhs);
+            row
+        })
+        .collect::<Vec<_>>();
+
+    eliminate(&mut m, n).ok_or_else(|| "matrix is singular".to_string())?;
+
+    let mut x = vec![vec![0.; width]; n];
+    for row in (0..n).rev() {
+        for c in 0..width {
+            let known = (row + 1..n).map(|k| m[row][k] * x[k][c]).sum::<f64>();
+            x[row][c] = (m[row][n + c] - known) / m[row][row];
+        }
+    }
+    Ok(x)
+}
+
+fn floats(rows: Vec<Vec<f64>>) -> Vec<Vec<lovm::Value>> {
+    rows.into_iter()
+        .map(|row| row.into_iter().map(lovm::Value::F64).collect())
+        .collect()
+}
+
+fn vadd(data: &mut vm::VmData, args: &[lovm::Value]) -> NativeResult {
+    let a = Tensor::load(data, &args[0])?;
+    let b = Tensor::load(data, &args[1])?;
+    Ok(a.zip_with(b, &add)?.store(data))
+}
+
+fn vsub(data: &mut vm::VmData, args: &[lovm::Value]) -> NativeResult {
+    let a = Tensor::load(data, &args[0])?;
+    let b = Tensor::load(data, &args[1])?;
+    Ok(a.zip_with(b, &sub)?.store(data))
+}
+
+// `*` on tuples: scalar multiplication, dot product or matrix product depending on the shapes
+fn vmul(data: &mut vm::VmData, args: &[lovm::Value]) -> NativeResult {
+    let a = Tensor::load(data, &args[0])?;
+    let b = Tensor::load(data, &args[1])?;
+    let result = match (a, b) {
+        (Tensor::Scalar(a), Tensor::Scalar(b)) => Tensor::Scalar(mul(&a, &b)?),
+        (Tensor::Scalar(k), t) | (t, Tensor::Scalar(k)) => t.map(&|v| mul(&k, v))?,
+        (a, b) => match (a.is_matrix(), b.is_matrix()) {
+            (true, true) => matrix_product(a.into_matrix()?, b.into_matrix()?)?,
+            (true, false) => {
+                let column = b.into_vector()?.into_iter().map(|v| vec![v]).collect();
+                let product = matrix_product(a.into_matrix()?, column)?.into_matrix()?;
+                Tensor::from_vector(product.into_iter().flatten().collect())
+            }
+            (false, true) => {
+                let row = vec![a.into_vector()?];
+                let product = matrix_product(row, b.into_matrix()?)?.into_matrix()?;
+                Tensor::from_vector(product.into_iter().flatten().collect())
+            }
+            (false, false) => Tensor::Scalar(dot_product(&a.into_vector()?, &b.into_vector()?)?),
+        },
+    };
+    Ok(result.store(data))
+}
+
+fn scale(data: &mut vm::VmData, args: &[lovm::Value]) -> NativeResult {
+    let k = args[0].clone();
+    let t = Tensor::load(data, &args[1])?;
+    Ok(t.map(&|v| mul(&k, v))?.store(data))
+}
+
+fn dot(data: &mut vm::VmData, args: &[lovm::Value]) -> NativeResult {
+    let a = Tensor::load(data, &args[0])?.into_vector()?;
+    let b = Tensor::load(data, &args[1])?.into_vector()?;
+    dot_product(&a, &b)
+}
+
+fn matmul(data: &mut vm::VmData, args: &[lovm::Value]) -> NativeResult {
+    let a = Tensor::load(data, &args[0])?.into_matrix()?;
+    let b = Tensor::load(data, &args[1])?.into_matrix()?;
+    Ok(matrix_product(a, b)?.store(data))
+}
+
+fn transpose(data: &mut vm::VmData, args: &[lovm::Value]) -> NativeResult {
+    let m = Tensor::load(data, &args[0])?.into_matrix()?;
+    Ok(Tensor::from_matrix(transpose_rows(m)).store(data))
+}
+
+fn det(data: &mut vm::VmData, args: &[lovm::Value]) -> NativeResult {
+    let mut m = float_matrix(Tensor::load(data, &args[0])?.into_matrix()?)?;
+    let n = square(&m)?;
+    Ok(lovm::Value::F64(eliminate(&mut m, n).unwrap_or(0.)))
+}
+
+fn inv(data: &mut vm::VmData, args: &[lovm::Value]) -> NativeResult {
+    let m = float_matrix(Tensor::load(data, &args[0])?.into_matrix()?)?;
+    let n = square(&m)?;
+    let identity = (0..n)
+        .map(|r| (0..n).map(|c| if r == c { 1. } else { 0. }).collect())
+        .collect();
+    let inverse = solve_columns(m, identity)?;
+    Ok(Tensor::from_matrix(floats(inverse)).store(data))
+}
+
+// solve the linear system `a x = b` where `b` is either a vector or a matrix
+fn lsolve(data: &mut vm::VmData, args: &[lovm::Value]) -> NativeResult {
+    let a = float_matrix(Tensor::load(data, &args[0])?.into_matrix()?)?;
+    let b = Tensor::load(data, &args[1])?;
+    let result = if b.is_matrix() {
+        let b = float_matrix(b.into_matrix()?)?;
+        Tensor::from_matrix(floats(solve_columns(a, b)?))
+    } else {
+        let b = b
+            .into_vector()?
+            .iter()
+            .map(|v| Ok(vec![native::number(v)?]))
+            .collect::<Result<Vec<_>, String>>()?;
+        let x = solve_columns(a, b)?;
+        Tensor::from_vector(x.into_iter().flatten().map(lovm::Value::F64).collect())
+    };
+    Ok(result.store(data))
+}
+
+// rewrite `+`, `-` and `*` into calls of the element-wise builtins unless both operands are known to
+// be scalars. the builtins decide at runtime whether they combine numbers or tuples, so
+// parameters, calls and globals may hold either. `is_pure` tells whether a function always
+// returns a number.
+pub fn lower<F>(expr: &Expr, is_pure: &F) -> Expr
+where
+    F: Fn(&str) -> bool,
+{
+    match expr {
+        Expr::Comp(op, lhs, rhs) => {
+            let lhs = lower(lhs, is_pure);
+            let rhs = lower(rhs, is_pure);
+            let name = match op {
+                Operator::Add => Some("@add"),
+                Operator::Sub => Some("@sub"),
+                Operator::Mul => Some("@mul"),
+                _ => None,
+            };
+            match name {
+                Some(name) if !is_scalar(&lhs, is_pure) || !is_scalar(&rhs, is_pure) => {
+                    Expr::Func(name.to_string(), vec![lhs, rhs])
+                }
+                _ => Expr::Comp(op.clone(), Box::new(lhs), Box::new(rhs)),
+            }
+        }
+        Expr::Func(name, args) => Expr::Func(
+            name.clone(),
+            args.iter().map(|arg| lower(arg, is_pure)).collect(),
+        ),
+        Expr::Value(Value::Tuple(items)) => Expr::Value(Value::Tuple(
+            items.iter().map(|item| lower(item, is_pure)).collect(),
+        )),
+        other => other.clone(),
+    }
+}
+
+// true if `expr` cannot evaluate to a tuple. operators left after lowering never return one.
+fn is_scalar<F>(expr: &Expr, is_pure: &F) -> bool
+where
+    F: Fn(&str) -> bool,
+{
+    match expr {
+        Expr::Value(Value::Tuple(_)) | Expr::Value(Value::Set(_)) => false,
+        Expr::Value(_) | Expr::Comp(..) => true,
+        Expr::Func(name, _) => is_pure(name),
+        Expr::Ref(_) => false,
+    }
+}
@@ -0,0 +1,352 @@
+use super::*;
+
+use localc_cc_lib::optimize::PureFn;
+
+use std::f64::consts;
+
+// numeric functions. all of them are pure and therefore also evaluated at compile time.
+// functions return `None` for arguments outside of their domain; results that are not
+// finite count as outside of the domain as well. integer arguments produce integer results
+// where the result is exact: `sqrt(16)` is `4`, `sqrt(2)` is a float.
+
+// constants cannot be reassigned, so `e = 2` is an error. functions named like a constant,
+// e.g. `e(x) = x`, are allowed.
+pub(super) const CONSTANTS: &[(&str, f64)] = &[("pi", consts::PI), ("e", consts::E)];
+
+pub(super) const PURE: &[(&str, usize, PureFn)] = &[
+    ("sqrt", 1, sqrt),
+    ("sqrt", 2, root),
+    ("exp", 1, exp),
+    ("ln", 1, ln),
+    ("log", 1, log10),
+    ("log", 2, log),
+    ("log2", 1, log2),
+    ("sin", 1, sin),
+    ("cos", 1, cos),
+    ("tan", 1, tan),
+    ("asin", 1, asin),
+    ("acos", 1, acos),
+    ("atan", 1, atan),
+    ("atan", 2, atan2),
+    ("sinh", 1, sinh),
+    ("cosh", 1, cosh),
+    ("tanh", 1, tanh),
+    ("asinh", 1, asinh),
+    ("acosh", 1, acosh),
+    ("atanh", 1, atanh),
+    ("floor", 1, floor),
+    ("ceil", 1, ceil),
+    ("round", 1, round),
+    ("round", 2, round_digits),
+    ("abs", 1, abs),
+    ("gcd", 2, gcd),
+    ("lcm", 2, lcm),
+];
+
+// `min` and `max` of a single tuple compare its items
+pub(super) const FUNCTIONS: &[(&str, usize, Builtin)] = &[("min", 1, min_of), ("max", 1, max_of)];
+
+// `min` and `max` of any other number of numbers
+pub(super) const VARIADIC: &[(&str, PureFn)] = &[("min", min), ("max", max)];
+
+fn float(n: &NumType) -> f64 {
+    match n {
+        NumType::Natural(n) => *n as f64,
+        NumType::Rational(n) => *n,
+    }
+}
+
+fn real(n: f64) -> Option<NumType> {
+    if n.is_finite() {
+        Some(NumType::Rational(n))
+    } else {
+        None
+    }
+}
+
+// convert a float without fraction into an integer
+fn integral(n: f64) -> Option<NumType> {
+    if n.is_finite() && (i64::min_value() as f64) <= n && n < (i64::max_value() as f64) {
+        Some(NumType::Natural(n as i64))
+    } else {
+        None
+    }
+}
+
+fn unary(args: &[NumType], f: fn(f64) -> f64) -> Option<NumType> {
+    real(f(float(&args[0])))
+}
+
+// the exact integer `k` with `k ^ n == x`, if any
+fn exact_root(x: i64, n: u32) -> Option<i64> {
+    let guess = (x as f64).powf(1. / f64::from(n)).round() as i64;
+    (guess.max(1) - 1..=guess.saturating_add(1)).find(|k| k.checked_pow(n) == Some(x))
+}
+
+fn nth_root(x: f64, n: f64) -> f64 {
+    match n {
+        _ if n == 2. => x.sqrt(),
+        _ if n == 3. => x.cbrt(),
+        _ => x.powf(1. / n),
+    }
+}
+
+fn sqrt(args: &[NumType]) -> Option<NumType> {
+    root(&[args[0].clone(), NumType::Natural(2)])
+}
+
+// `n`-th root of `x`. odd roots of negative numbers are negative.
+fn root(args: &[NumType]) -> Option<NumType> {
+    match (&args[0], &args[1]) {
+        (NumType::Natural(x), NumType::Natural(n))
+            if 0 < *n && *n <= i64::from(u32::max_value()) =>
+        {
+            let n = *n as u32;
+            if *x < 0 && n % 2 == 0 {
+                return None;
+            }
+            match x.checked_abs().and_then(|abs| exact_root(abs, n)) {
+                Some(k) if *x < 0 => Some(NumType::Natural(-k)),
+                Some(k) => Some(NumType::Natural(k)),
+                _ => {
+                    let x = *x as f64;
+                    real(x.signum() * nth_root(x.abs(), f64::from(n)))
+                }
+            }
+        }
+        (x, n) => {
+            let (x, n) = (float(x), float(n));
+            if x < 0. || n == 0. {
+                return None;
+            }
+            real(nth_root(x, n))
+        }
+    }
+}
+
+fn exp(args: &[NumType]) -> Option<NumType> {
+    unary(args, f64::exp)
+}
+
+fn ln(args: &[NumType]) -> Option<NumType> {
+    unary(args, f64::ln)
+}
+
+fn log10(args: &[NumType]) -> Option<NumType> {
+    unary(args, f64::log10)
+}
+
+fn log2(args: &[NumType]) -> Option<NumType> {
+    unary(args, f64::log2)
+}
+
+// logarithm of `x` to base `b`
+fn log(args: &[NumType]) -> Option<NumType> {
+    let (x, b) = (float(&args[0]), float(&args[1]));
+    if b <= 0. || b == 1. {
+        return None;
+    }
+    // exact for powers of the common bases
+    let n = match b {
+        _ if b == 2. => x.log2(),
+        _ if b == 10. => x.log10(),
+        _ => x.ln() / b.ln(),
+    };
+    real(n)
+}
+
+fn sin(args: &[NumType]) -> Option<NumType> {
+    unary(args, f64::sin)
+}
+
+fn cos(args: &[NumType]) -> Option<NumType> {
+    unary(args, f64::cos)
+}
+
+fn tan(args: &[NumType]) -> Option<NumType> {
+    unary(args, f64::tan)
+}
+
+fn asin(args: &[NumType]) -> Option<NumType> {
+    unary(args, f64::asin)
+}
+
+fn acos(args: &[NumType]) -> Option<NumType> {
+    unary(args, f64::acos)
+}
+
+fn atan(args: &[NumType]) -> Option<NumType> {
+    unary(args, f64::atan)
+}
+
+// angle of the point `(x, y)` given as `atan(y, x)`
+fn atan2(args: &[NumType]) -> Option<NumType> {
+    real(float(&args[0]).atan2(float(&args[1])))
+}
+
+fn sinh(args: &[NumType]) -> Option<NumType> {
+    unary(args, f64::sinh)
+}
+
+fn cosh(args: &[NumType]) -> Option<NumType> {
+    unary(args, f64::cosh)
+}
+
+fn tanh(args: &[NumType]) -> Option<NumType> {
+    unary(args, f64::tanh)
+}
+
+fn asinh(args: &[NumType]) -> Option<NumType> {
+    unary(args, f64::asinh)
+}
+
+fn acosh(args: &[NumType]) -> Option<NumType> {
+    unary(args, f64::acosh)
+}
+
+fn atanh(args: &[NumType]) -> Option<NumType> {
+    unary(args, f64::atanh)
+}
+
+fn floor(args: &[NumType]) -> Option<NumType> {
+    match &args[0] {
+        NumType::Natural(n) => Some(NumType::Natural(*n)),
+        NumType::Rational(n) => integral(n.floor()),
+    }
+}
+
+fn ceil(args: &[NumType]) -> Option<NumType> {
+    match &args[0] {
+        NumType::Natural(n) => Some(NumType::Natural(*n)),
+        NumType::Rational(n) => integral(n.ceil()),
+    }
+}
+
+fn round(args: &[NumType]) -> Option<NumType> {
+    match &args[0] {
+        NumType::Natural(n) => Some(NumType::Natural(*n)),
+        NumType::Rational(n) => integral(n.round()),
+    }
+}
+
+// round to `digits` after the decimal point. negative digits round to tens, hundreds, ...
+fn round_digits(args: &[NumType]) -> Option<NumType> {
+    let digits = match &args[1] {
+        NumType::Natural(digits) if digits.abs() <= 308 => *digits as i32,
+        _ => return None,
+    };
+    match &args[0] {
+        NumType::Natural(n) if 0 <= digits => Some(NumType::Natural(*n)),
+        // halves are rounded away from zero like `f64::round` does
+        NumType::Natural(n) if digits < 0 => {
+            let scale = match 10i64.checked_pow(-digits as u32) {
+                Some(scale) => scale,
+                _ => return Some(NumType::Natural(0)),
+            };
+            let (quot, rem) = (n / scale, (n % scale).abs());
+            let quot = if scale - rem <= rem {
+                quot + n.signum()
+            } else {
+                quot
+            };
+            quot.checked_mul(scale).map(NumType::Natural)
+        }
+        // dividing by a power of ten avoids inexact scales like `0.01`
+        n if digits < 0 => {
+            let scale = 10f64.powi(-digits);
+            real((float(n) / scale).round() * scale)
+        }
+        n => {
+            let scale = 10f64.powi(digits);
+            real((float(n) * scale).round() / scale)
+        }
+    }
+}
+
+fn abs(args: &[NumType]) -> Option<NumType> {
+    match &args[0] {
+        NumType::Natural(n) => n.checked_abs().map(NumType::Natural),
+        NumType::Rational(n) => Some(NumType::Rational(n.abs())),
+    }
+}
+
+fn naturals(args: &[NumType]) -> Option<(i64, i64)> {
+    match (&args[0], &args[1]) {
+        (NumType::Natural(a), NumType::Natural(b)) => Some((*a, *b)),
+        _ => None,
+    }
+}
+
+fn euclid(a: i64, b: i64) -> Option<i64> {
+    let (mut a, mut b) = (a.checked_abs()?, b.checked_abs()?);
+    while b != 0 {
+        let r = a % b;
+        a = b;
+        b = r;
+    }
+    Some(a)
+}
+
+fn gcd(args: &[NumType]) -> Option<NumType> {
+    let (a, b) = naturals(args)?;
+    euclid(a, b).map(NumType::Natural)
+}
+
+fn lcm(args: &[NumType]) -> Option<NumType> {
+    let (a, b) = naturals(args)?;
+    if a == 0 || b == 0 {
+        return Some(NumType::Natural(0));
+    }
+    let gcd = euclid(a, b)?;
+    (a / gcd)
+        .checked_mul(b)?
+        .checked_abs()
+        .map(NumType::Natural)
+}
+
+// the smallest or largest number; integers stay integers
+fn extreme(args: &[NumType], pick: fn(f64, f64) -> bool) -> Option<NumType> {
+    let mut best = args.first()?;
+    for arg in args.iter().skip(1) {
+        if pick(float(arg), float(best)) {
+            best = arg;
+        }
+    }
+    Some(best.clone())
+}
+
+fn min(args: &[NumType]) -> Option<NumType> {
+    extreme(args, |a, b| a < b)
+}
+
+fn max(args: &[NumType]) -> Option<NumType> {
+    extreme(args, |a, b| b < a)
+}
+
+// apply `f` to the items of a tuple or to a single number
+fn over_items(data: &vm::VmData, value: &lovm::Value, f: PureFn) -> NativeResult {
+    let items = match value {
+        lovm::Value::Ref(_) => native::array(data, value)?,
+        other => vec![other.clone()],
+    };
+    let args = items
+        .iter()
+        .map(|item| match item {
+            lovm::Value::I64(n) => Ok(NumType::Natural(*n)),
+            lovm::Value::F64(n) => Ok(NumType::Rational(*n)),
+            other => Err(format!("expected number, got `{:?}`", other)),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    match f(&args) {
+        Some(result) => Ok(result.into()),
+        _ => Err("expected at least one number".to_string()),
+    }
+}
+
+fn min_of(data: &mut vm::VmData, args: &[lovm::Value]) -> NativeResult {
+    over_items(data, &args[0], min)
+}
+
+fn max_of(data: &mut vm::VmData, args: &[lovm::Value]) -> NativeResult {
+    over_items(data, &args[0], max)
+}
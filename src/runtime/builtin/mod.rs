@@ -0,0 +1,48 @@
+pub mod base;
+pub mod collection;
+pub mod io;
+pub mod linalg;
+pub mod math;
+pub mod stats;
+pub mod table;
+
+use super::*;
+
+// signature of functions that are shipped with the runtime
+pub type Builtin = fn(&mut vm::VmData, &[lovm::Value]) -> NativeResult;
+
+// register all builtin libraries on `runtime`. functions in the `EFFECTS` tables have side
+// effects and are registered as impure.
+pub fn install(runtime: &mut Runtime) {
+    let tables = [
+        (base::FUNCTIONS, true),
+        (collection::FUNCTIONS, true),
+        (io::EFFECTS, false),
+        (linalg::FUNCTIONS, true),
+        (math::FUNCTIONS, true),
+        (stats::FUNCTIONS, true),
+        (table::FUNCTIONS, true),
+        (table::EFFECTS, false),
+    ];
+    for (table, pure) in tables.iter() {
+        for (name, arity, builtin) in table.iter() {
+            let builtin = std::sync::Arc::new(*builtin);
+            runtime.define_native(&name.to_string(), *arity, builtin, *pure);
+        }
+    }
+
+    for (name, value) in math::CONSTANTS.iter() {
+        runtime
+            .define_const(
+                &name.to_string(),
+                ast::Value::Numeric(NumType::Rational(*value)),
+            )
+            .unwrap();
+    }
+    for (name, arity, func) in math::PURE.iter() {
+        runtime.define_pure(&name.to_string(), *arity, *func);
+    }
+    for (name, func) in math::VARIADIC.iter() {
+        runtime.define_pure_variadic(&name.to_string(), *func);
+    }
+}
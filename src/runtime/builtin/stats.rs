@@ -0,0 +1,247 @@
+use super::*;
+
+use std::cmp::Ordering;
+
+// descriptive statistics over tuples. functions of one sample only look at the numbers of a
+// tuple so they work on csv columns with empty cells. functions of two samples pair items by
+// position and therefore expect tuples of numbers with equal length. variances are sample
+// variances dividing by `n - 1`.
+
+pub(super) const FUNCTIONS: &[(&str, usize, Builtin)] = &[
+    ("sum", 1, sum),
+    ("product", 1, product),
+    ("mean", 1, mean),
+    ("median", 1, median),
+    ("mode", 1, mode),
+    ("variance", 1, variance),
+    ("stddev", 1, stddev),
+    ("quantile", 2, quantile),
+    ("covariance", 2, covariance),
+    ("correlation", 2, correlation),
+    ("regression", 2, regression),
+];
+
+const NO_NUMBERS: &str = "expected a tuple containing numbers";
+
+// numeric items of a tuple
+pub(super) fn numbers(data: &vm::VmData, value: &lovm::Value) -> Result<Vec<lovm::Value>, String> {
+    Ok(native::array(data, value)?
+        .into_iter()
+        .filter(|item| native::number(item).is_ok())
+        .collect())
+}
+
+fn floats(items: &[lovm::Value]) -> Result<Vec<f64>, String> {
+    items.iter().map(native::number).collect()
+}
+
+// numbers of a tuple that must contain at least `min` of them
+fn sample(data: &vm::VmData, value: &lovm::Value, min: usize) -> Result<Vec<f64>, String> {
+    let sample = floats(&numbers(data, value)?)?;
+    if sample.is_empty() {
+        return Err(NO_NUMBERS.to_string());
+    }
+    if sample.len() < min {
+        return Err(format!(
+            "expected at least {} numbers, got {}",
+            min,
+            sample.len()
+        ));
+    }
+    Ok(sample)
+}
+
+fn sorted(mut sample: Vec<f64>) -> Vec<f64> {
+    sample.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+    sample
+}
+
+// two samples of equal length
+fn paired(
+    data: &vm::VmData,
+    x: &lovm::Value,
+    y: &lovm::Value,
+) -> Result<(Vec<f64>, Vec<f64>), String> {
+    let x = floats(&native::array(data, x)?)?;
+    let y = floats(&native::array(data, y)?)?;
+    if x.len() != y.len() {
+        return Err(format!(
+            "samples differ in length: {} and {}",
+            x.len(),
+            y.len()
+        ));
+    }
+    if x.len() < 2 {
+        return Err("expected at least 2 pairs".to_string());
+    }
+    Ok((x, y))
+}
+
+fn average(sample: &[f64]) -> f64 {
+    sample.iter().sum::<f64>() / sample.len() as f64
+}
+
+// sum of products of the deviations from the means
+fn comoment(x: &[f64], y: &[f64]) -> f64 {
+    let (mx, my) = (average(x), average(y));
+    x.iter()
+        .zip(y.iter())
+        .map(|(a, b)| (a - mx) * (b - my))
+        .sum()
+}
+
+// the items as integers or `None` if there are floats
+fn integers(items: &[lovm::Value]) -> Option<Vec<i64>> {
+    items
+        .iter()
+        .map(|item| match item {
+            lovm::Value::I64(n) => Some(*n),
+            _ => None,
+        })
+        .collect()
+}
+
+// the sum stays an integer if all items are integers
+fn sum(data: &mut vm::VmData, args: &[lovm::Value]) -> NativeResult {
+    let items = numbers(data, &args[0])?;
+    match integers(&items) {
+        Some(integers) => integers
+            .into_iter()
+            .try_fold(0i64, i64::checked_add)
+            .map(lovm::Value::I64)
+            .ok_or_else(|| "integer overflow in sum".to_string()),
+        _ => Ok(lovm::Value::F64(floats(&items)?.into_iter().sum())),
+    }
+}
+
+fn product(data: &mut vm::VmData, args: &[lovm::Value]) -> NativeResult {
+    let items = numbers(data, &args[0])?;
+    match integers(&items) {
+        Some(integers) => integers
+            .into_iter()
+            .try_fold(1i64, i64::checked_mul)
+            .map(lovm::Value::I64)
+            .ok_or_else(|| "integer overflow in product".to_string()),
+        _ => Ok(lovm::Value::F64(floats(&items)?.into_iter().product())),
+    }
+}
+
+fn mean(data: &mut vm::VmData, args: &[lovm::Value]) -> NativeResult {
+    Ok(lovm::Value::F64(average(&sample(data, &args[0], 1)?)))
+}
+
+// the middle item keeps its type, the average of the two middle items is a float
+fn median(data: &mut vm::VmData, args: &[lovm::Value]) -> NativeResult {
+    let mut items = numbers(data, &args[0])?;
+    if items.is_empty() {
+        return Err(NO_NUMBERS.to_string());
+    }
+    items.sort_by(|a, b| {
+        let (a, b) = (native::number(a).unwrap(), native::number(b).unwrap());
+        a.partial_cmp(&b).unwrap_or(Ordering::Equal)
+    });
+    let mid = items.len() / 2;
+    if items.len() % 2 == 1 {
+        return Ok(items[mid].clone());
+    }
+    let (a, b) = (
+        native::number(&items[mid - 1])?,
+        native::number(&items[mid])?,
+    );
+    Ok(lovm::Value::F64((a + b) / 2.))
+}
+
+// most frequent number. ties are resolved in favor of the smallest number.
+fn mode(data: &mut vm::VmData, args: &[lovm::Value]) -> NativeResult {
+    let items = numbers(data, &args[0])?;
+    let mut best: Option<(&lovm::Value, f64, usize)> = None;
+    for item in items.iter() {
+        let n = native::number(item)?;
+        let count = items
+            .iter()
+            .filter(|other| native::number(other) == Ok(n))
+            .count();
+        let better = match best {
+            Some((_, m, most)) => most < count || (most == count && n < m),
+            _ => true,
+        };
+        if better {
+            best = Some((item, n, count));
+        }
+    }
+    match best {
+        Some((item, _, _)) => Ok(item.clone()),
+        _ => Err(NO_NUMBERS.to_string()),
+    }
+}
+
+fn sample_variance(sample: &[f64]) -> f64 {
+    comoment(sample, sample) / (sample.len() - 1) as f64
+}
+
+fn variance(data: &mut vm::VmData, args: &[lovm::Value]) -> NativeResult {
+    Ok(lovm::Value::F64(sample_variance(&sample(
+        data, &args[0], 2,
+    )?)))
+}
+
+fn stddev(data: &mut vm::VmData, args: &[lovm::Value]) -> NativeResult {
+    Ok(lovm::Value::F64(
+        sample_variance(&sample(data, &args[0], 2)?).sqrt(),
+    ))
+}
+
+// the `q`-quantile interpolated linearly between the closest ranks
+fn quantile(data: &mut vm::VmData, args: &[lovm::Value]) -> NativeResult {
+    let sample = sorted(sample(data, &args[0], 1)?);
+    let q = native::number(&args[1])?;
+    if !(0. <= q && q <= 1.) {
+        return Err(format!("quantile must be between 0 and 1, got {}", q));
+    }
+    let rank = q * (sample.len() - 1) as f64;
+    let (lower, upper) = (rank.floor() as usize, rank.ceil() as usize);
+    let fraction = rank - lower as f64;
+    Ok(lovm::Value::F64(
+        sample[lower] + (sample[upper] - sample[lower]) * fraction,
+    ))
+}
+
+fn covariance(data: &mut vm::VmData, args: &[lovm::Value]) -> NativeResult {
+    let (x, y) = paired(data, &args[0], &args[1])?;
+    Ok(lovm::Value::F64(comoment(&x, &y) / (x.len() - 1) as f64))
+}
+
+fn pearson(x: &[f64], y: &[f64]) -> Result<f64, String> {
+    let spread = (comoment(x, x) * comoment(y, y)).sqrt();
+    if spread == 0. {
+        return Err("correlation of a constant sample".to_string());
+    }
+    Ok(comoment(x, y) / spread)
+}
+
+fn correlation(data: &mut vm::VmData, args: &[lovm::Value]) -> NativeResult {
+    let (x, y) = paired(data, &args[0], &args[1])?;
+    Ok(lovm::Value::F64(pearson(&x, &y)?))
+}
+
+// least squares fit of `y = slope * x + intercept`. returns `(slope, intercept, r2)`.
+fn regression(data: &mut vm::VmData, args: &[lovm::Value]) -> NativeResult {
+    let (x, y) = paired(data, &args[0], &args[1])?;
+    let sxx = comoment(&x, &x);
+    if sxx == 0. {
+        return Err("regression over a constant sample".to_string());
+    }
+    let slope = comoment(&x, &y) / sxx;
+    let intercept = average(&y) - slope * average(&x);
+    // a constant `y` is fitted perfectly
+    let r2 = match pearson(&x, &y) {
+        Ok(r) => r * r,
+        _ => 1.,
+    };
+    let fit = vec![
+        lovm::Value::F64(slope),
+        lovm::Value::F64(intercept),
+        lovm::Value::F64(r2),
+    ];
+    Ok(native::alloc_array(data, fit))
+}
@@ -0,0 +1,241 @@
+use super::*;
+
+use std::fs::File;
+use std::io::{Read, Write};
+
+// tabular data read from and written to csv files. a table is a tuple of row tuples, its
+// first row usually being the header. cells that look like numbers are read as numbers,
+// all other cells as strings. like in spreadsheets, `count` and the statistics functions only
+// look at the numeric cells of a column.
+
+pub(super) const FUNCTIONS: &[(&str, usize, Builtin)] =
+    &[("column", 2, column), ("count", 1, count)];
+
+// functions reading or writing files
+pub(super) const EFFECTS: &[(&str, usize, Builtin)] = &[
+    ("csv", 1, csv),
+    ("csv", 2, csv),
+    ("write_csv", 2, write_csv),
+];
+
+// split `content` into records of fields. fields may be quoted with `"` to contain commas,
+// line breaks and quotes, which are escaped by doubling them. blank lines are skipped.
+pub fn parse_csv(content: &str) -> Result<Vec<Vec<String>>, String> {
+    let mut records = vec![];
+    let mut record = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    // nothing but the line break was read for the current record
+    let mut blank = true;
+    let mut chars = content.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '\n' && c != '\r' {
+            blank = false;
+        }
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' if quoted => quoted = false,
+            '"' if field.is_empty() => quoted = true,
+            ',' if !quoted => record.push(std::mem::replace(&mut field, String::new())),
+            '\r' if !quoted && chars.peek() == Some(&'\n') => {}
+            '\n' if !quoted => {
+                record.push(std::mem::replace(&mut field, String::new()));
+                let record = std::mem::replace(&mut record, vec![]);
+                if !std::mem::replace(&mut blank, true) {
+                    records.push(record);
+                }
+            }
+            c => field.push(c),
+        }
+    }
+
+    if quoted {
+        return Err("unterminated quoted field".to_string());
+    }
+    // the last line may lack its line break
+    if !blank {
+        record.push(field);
+        records.push(record);
+    }
+
+    Ok(records)
+}
+
+// read a cell as integer, float or string
+fn cell(field: &str) -> lovm::Value {
+    let trimmed = field.trim();
+    let num = match (trimmed.parse::<i64>(), trimmed.parse::<f64>()) {
+        (Ok(n), _) => Some(NumType::Natural(n)),
+        // `inf` and `nan` are text in spreadsheets
+        (_, Ok(n)) if n.is_finite() => Some(NumType::Rational(n)),
+        _ => None,
+    };
+    match num {
+        Some(num) => num.into(),
+        _ => lovm::Value::Str(field.into()),
+    }
+}
+
+fn path(value: &lovm::Value) -> Result<String, String> {
+    match value {
+        lovm::Value::Str(path) => Ok(path.to_string()),
+        other => Err(format!("expected path, got `{:?}`", other)),
+    }
+}
+
+// `csv(path)` reads all rows into a tuple of row tuples. `csv(path, true)` uses the first row
+// as header and returns a set that maps every header to the tuple of its column.
+fn csv(data: &mut vm::VmData, args: &[lovm::Value]) -> NativeResult {
+    let path = path(&args[0])?;
+    let header = match args.get(1) {
+        Some(lovm::Value::T(header)) => *header,
+        Some(other) => return Err(format!("expected logical value, got `{:?}`", other)),
+        _ => false,
+    };
+
+    let mut content = String::new();
+    File::open(&path)
+        .and_then(|mut file| file.read_to_string(&mut content))
+        .map_err(|err| format!("could not read `{}`: {}", path, err))?;
+    let records = parse_csv(&content).map_err(|err| format!("invalid csv `{}`: {}", path, err))?;
+
+    if !header {
+        let rows = records
+            .iter()
+            .map(|record| {
+                let cells = record.iter().map(|field| cell(field)).collect();
+                native::alloc_array(data, cells)
+            })
+            .collect();
+        return Ok(native::alloc_array(data, rows));
+    }
+
+    let mut records = records.into_iter();
+    let names = records.next().unwrap_or_default();
+    // the columns of duplicate headers would overwrite each other in the set
+    for (i, name) in names.iter().enumerate() {
+        if names[..i].iter().any(|other| other.trim() == name.trim()) {
+            return Err(format!("duplicate header `{}` in `{}`", name.trim(), path));
+        }
+    }
+    let mut columns = vec![vec![]; names.len()];
+    for (i, record) in records.enumerate() {
+        if record.len() != names.len() {
+            return Err(format!(
+                "row {} of `{}` has {} fields, expected {}",
+                i + 2,
+                path,
+                record.len(),
+                names.len()
+            ));
+        }
+        for (column, field) in columns.iter_mut().zip(record.iter()) {
+            column.push(cell(field));
+        }
+    }
+
+    let entries = names
+        .into_iter()
+        .zip(columns.into_iter())
+        .map(|(name, column)| {
+            (
+                lovm::Value::Str(name.trim().into()),
+                native::alloc_array(data, column),
+            )
+        })
+        .collect();
+    Ok(native::alloc_dict(data, entries))
+}
+
+fn write_field(out: &mut String, data: &vm::VmData, value: &lovm::Value) {
+    let field = match value {
+        lovm::Value::Str(s) => s.to_string(),
+        // nested tuples are written completely
+        other => Pretty::new()
+            .with_max_items(usize::max_value())
+            .format(data, other),
+    };
+    if field.contains(|c| c == ',' || c == '"' || c == '\n' || c == '\r') {
+        out.push('"');
+        out.push_str(&field.replace('"', "\"\""));
+        out.push('"');
+    } else {
+        out.push_str(&field);
+    }
+}
+
+// write a tuple of rows to a file. rows that are no tuples become rows with a single field.
+// returns the number of rows written.
+fn write_csv(data: &mut vm::VmData, args: &[lovm::Value]) -> NativeResult {
+    let path = path(&args[0])?;
+    let rows = native::array(data, &args[1])?;
+
+    let mut out = String::new();
+    for row in rows.iter() {
+        let fields = match row {
+            lovm::Value::Ref(_) => native::array(data, row)?,
+            other => vec![other.clone()],
+        };
+        for (i, field) in fields.iter().enumerate() {
+            if 0 < i {
+                out.push(',');
+            }
+            write_field(&mut out, data, field);
+        }
+        out.push('\n');
+    }
+
+    File::create(&path)
+        .and_then(|mut file| file.write_all(out.as_bytes()))
+        .map_err(|err| format!("could not write `{}`: {}", path, err))?;
+    Ok(lovm::Value::I64(rows.len() as i64))
+}
+
+// `column(table, i)` selects the i-th field of every row. `column(table, name)` selects the
+// column below the header `name` of a table or the column `name` of a set read with header.
+fn column(data: &mut vm::VmData, args: &[lovm::Value]) -> NativeResult {
+    if let Ok(columns) = native::dict(data, &args[0]) {
+        return columns
+            .get(&args[1])
+            .cloned()
+            .ok_or_else(|| format!("no column `{:?}`", args[1]));
+    }
+
+    let rows = native::array(data, &args[0])?
+        .iter()
+        .map(|row| native::array(data, row))
+        .collect::<Result<Vec<_>, _>>()?;
+    let (index, rows) = match &args[1] {
+        lovm::Value::I64(index) if 0 <= *index => (*index as usize, &rows[..]),
+        name @ lovm::Value::Str(_) => {
+            let header = rows.first().ok_or("table has no header")?;
+            let index = header
+                .iter()
+                .position(|field| field == name)
+                .ok_or_else(|| format!("no column `{:?}`", name))?;
+            (index, &rows[1..])
+        }
+        other => return Err(format!("expected column index or name, got `{:?}`", other)),
+    };
+
+    let column = rows
+        .iter()
+        .enumerate()
+        .map(|(i, row)| {
+            row.get(index)
+                .cloned()
+                .ok_or_else(|| format!("row {} has no column {}", i, index))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(native::alloc_array(data, column))
+}
+
+fn count(data: &mut vm::VmData, args: &[lovm::Value]) -> NativeResult {
+    Ok(lovm::Value::I64(
+        stats::numbers(data, &args[0])?.len() as i64
+    ))
+}
@@ -0,0 +1,102 @@
+use super::*;
+
+use lovm::vm::object::*;
+
+// results of the vm reference tuples and sets through handles into the object pool. these
+// functions resolve them into owned `ast::Value` trees so embedders never have to look into
+// the vm.
+
+impl Runtime {
+    // materialize `value` into an owned tree. fails on values that have no localc equivalent
+    // and on objects that contain themselves.
+    pub fn to_value(&self, value: &lovm::Value) -> Result<ast::Value, String> {
+        to_value(&self.vm.data, value, &mut vec![])
+    }
+
+    // read the global `name` as owned value
+    pub fn global(&self, name: &str) -> Option<Result<ast::Value, String>> {
+        self.vm
+            .data
+            .globals
+            .get(name)
+            .map(|value| self.to_value(value))
+    }
+}
+
+fn to_value(
+    data: &vm::VmData,
+    value: &lovm::Value,
+    visited: &mut Vec<usize>,
+) -> Result<ast::Value, String> {
+    match value {
+        lovm::Value::I64(n) => Ok(ast::Value::Numeric(NumType::Natural(*n))),
+        lovm::Value::F64(n) => Ok(ast::Value::Numeric(NumType::Rational(*n))),
+        lovm::Value::T(t) => Ok(ast::Value::Logical(*t)),
+        lovm::Value::Str(s) => Ok(ast::Value::Str(s.clone())),
+        lovm::Value::Ref(handle) => {
+            if visited.contains(handle) {
+                return Err(format!("object `{}` contains itself", handle));
+            }
+            visited.push(*handle);
+            let result = match data.obj_pool.get(handle).map(|obj| &obj.inner) {
+                Some(ObjectKind::Array(array)) => array
+                    .inner()
+                    .iter()
+                    .map(|item| Ok(Expr::Value(to_value(data, item, visited)?)))
+                    .collect::<Result<Vec<_>, String>>()
+                    .map(ast::Value::Tuple),
+                Some(ObjectKind::Dict(dict)) => {
+                    // dicts are unordered; sort by key for stable results
+                    let mut entries = dict.inner().iter().collect::<Vec<_>>();
+                    entries.sort_by(|(a, _), (b, _)| cmp_keys(a, b));
+                    let entries = entries
+                        .into_iter()
+                        .map(|(key, value)| {
+                            let key = to_value(data, key, visited)?;
+                            let value = to_value(data, value, visited)?;
+                            Ok((key, value))
+                        })
+                        .collect::<Result<Vec<_>, String>>()?;
+                    Ok(ast::Value::Set(
+                        entries
+                            .into_iter()
+                            .map(|(key, value)| (Some(Expr::Value(key)), Expr::Value(value)))
+                            .collect(),
+                    ))
+                }
+                Some(other) => Err(format!("cannot convert object `{:?}`", other)),
+                _ => Err(format!("invalid reference `{}`", handle)),
+            };
+            visited.pop();
+            result
+        }
+        other => Err(format!("cannot convert `{:?}`", other)),
+    }
+}
+
+// total order of set keys: numbers by value, then logicals, strings and references
+pub fn cmp_keys(a: &lovm::Value, b: &lovm::Value) -> std::cmp::Ordering {
+    let rank = |value: &lovm::Value| match value {
+        lovm::Value::I64(_) => 0,
+        lovm::Value::F64(_) => 1,
+        lovm::Value::T(_) => 2,
+        lovm::Value::Str(_) => 3,
+        _ => 4,
+    };
+    match (a, b) {
+        (lovm::Value::I64(x), lovm::Value::I64(y)) => x.cmp(y),
+        (lovm::Value::I64(_), lovm::Value::F64(_))
+        | (lovm::Value::F64(_), lovm::Value::I64(_))
+        | (lovm::Value::F64(_), lovm::Value::F64(_)) => {
+            let (x, y) = (native::number(a).unwrap(), native::number(b).unwrap());
+            // `NaN` comes last and an integer comes before an equal float
+            x.partial_cmp(&y)
+                .unwrap_or_else(|| x.is_nan().cmp(&y.is_nan()))
+                .then_with(|| rank(a).cmp(&rank(b)))
+        }
+        (lovm::Value::T(x), lovm::Value::T(y)) => x.cmp(y),
+        (lovm::Value::Str(x), lovm::Value::Str(y)) => x.to_string().cmp(&y.to_string()),
+        (lovm::Value::Ref(x), lovm::Value::Ref(y)) => x.cmp(y),
+        _ => rank(a).cmp(&rank(b)),
+    }
+}
@@ -0,0 +1,172 @@
+use super::*;
+
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+
+// results of memoized functions per argument tuple. calls with tuples or sets as arguments are
+// never cached because the referenced objects may change.
+
+pub type MemoCache = Arc<Mutex<HashMap<Name, HashMap<Vec<lovm::Value>, lovm::Value>>>>;
+
+// output functions of the vm. other functions with side effects are natives that are
+// registered as impure.
+const OUTPUT: &[&str] = &["println", "print"];
+
+impl Runtime {
+    // cache the results of `name`. fails if `name` depends on an impure function.
+    pub fn memoize(&mut self, name: &Name) -> Result<(), String> {
+        if !self.fn_templates.contains_key(name) {
+            return Err(format!("function `{}` is not defined", name));
+        }
+        if let Some(dep) = self
+            .dependencies(name)
+            .into_iter()
+            .find(|dep| self.is_impure(dep))
+        {
+            return Err(format!("`{}` is not pure, it depends on `{}`", name, dep));
+        }
+
+        let memo = Memo {
+            lookup: self.register_memo_native(name, memo_lookup),
+            fetch: self.register_memo_native(name, memo_fetch),
+            store: self.register_memo_native(name, memo_store),
+        };
+
+        self.fn_templates.get_mut(name).unwrap().memoize(memo);
+        self.pending.insert(name.clone());
+
+        Ok(())
+    }
+
+    fn register_memo_native(&mut self, name: &Name, f: MemoFn) -> usize {
+        let cache = self.memo.clone();
+        let name = name.clone();
+        let native = move |_: &mut vm::VmData, args: &[lovm::Value]| f(&cache, &name, args);
+        let id = native::register(Arc::new(native), false);
+        self.natives.push(id);
+        id
+    }
+
+    // true if `name` is an output function or bound to an impure native
+    fn is_impure(&self, name: &Name) -> bool {
+        OUTPUT.contains(&name.as_str())
+            || self
+                .bindings
+                .get(name)
+                .map_or(false, |ids| ids.iter().any(|id| !native::is_pure(*id)))
+    }
+
+    // drop cached results of all memoized functions that depend on `name`
+    pub(crate) fn invalidate(&mut self, name: &Name) {
+        let memoized = self
+            .fn_templates
+            .iter()
+            .filter(|(_, fn_template)| fn_template.is_memoized())
+            .map(|(fname, _)| fname.clone())
+            .collect::<Vec<_>>();
+
+        for fname in memoized.into_iter() {
+            if self.dependencies(&fname).contains(name) {
+                if let Some(cache) = self.memo.lock().unwrap().get_mut(&fname) {
+                    cache.clear();
+                }
+            }
+        }
+    }
+
+    // names of all globals and functions `name` reads or calls, including `name` itself
+    pub fn dependencies(&self, name: &Name) -> HashSet<Name> {
+        let mut deps = HashSet::new();
+        let mut pending = vec![name.clone()];
+
+        while let Some(next) = pending.pop() {
+            if !deps.insert(next.clone()) {
+                continue;
+            }
+            if let Some(fn_template) = self.fn_templates.get(&next) {
+                for (overload, expr) in fn_template.definitions() {
+                    let params = overload.iter().cloned().collect::<Vec<_>>();
+                    let mut refs = HashSet::new();
+                    references(expr, &params, &mut refs);
+                    pending.extend(refs.into_iter().filter(|dep| !deps.contains(dep)));
+                }
+            }
+        }
+
+        deps
+    }
+}
+
+type MemoFn = fn(&MemoCache, &Name, &[lovm::Value]) -> NativeResult;
+
+fn is_cacheable(value: &lovm::Value) -> bool {
+    match value {
+        lovm::Value::Ref(_) => false,
+        _ => true,
+    }
+}
+
+fn memo_lookup(cache: &MemoCache, name: &Name, args: &[lovm::Value]) -> NativeResult {
+    let hit = cache
+        .lock()
+        .unwrap()
+        .get(name)
+        .map_or(false, |cache| cache.contains_key(args));
+    Ok(lovm::Value::T(hit))
+}
+
+fn memo_fetch(cache: &MemoCache, name: &Name, args: &[lovm::Value]) -> NativeResult {
+    cache
+        .lock()
+        .unwrap()
+        .get(name)
+        .and_then(|cache| cache.get(args).cloned())
+        .ok_or_else(|| format!("no cached result for `{}`", name))
+}
+
+// `args` is the result followed by the arguments of the call
+fn memo_store(cache: &MemoCache, name: &Name, args: &[lovm::Value]) -> NativeResult {
+    let (result, key) = args.split_first().ok_or("no result to cache")?;
+    if is_cacheable(result) && key.iter().all(is_cacheable) {
+        cache
+            .lock()
+            .unwrap()
+            .entry(name.clone())
+            .or_insert_with(HashMap::new)
+            .insert(key.to_vec(), result.clone());
+    }
+    Ok(result.clone())
+}
+
+// collect names of globals and functions used in `expr` that are not parameters
+fn references(expr: &Expr, params: &TupleType, refs: &mut HashSet<Name>) {
+    match expr {
+        Expr::Ref(name) if !params.contains(expr) => {
+            refs.insert(name.clone());
+        }
+        Expr::Comp(_, lhs, rhs) => {
+            references(lhs, params, refs);
+            references(rhs, params, refs);
+        }
+        Expr::Func(name, args) => {
+            refs.insert(name.clone());
+            for arg in args.iter() {
+                references(arg, params, refs);
+            }
+        }
+        Expr::Value(ast::Value::Tuple(items)) => {
+            for item in items.iter() {
+                references(item, params, refs);
+            }
+        }
+        Expr::Value(ast::Value::Set(items)) => {
+            for (key, value) in items.iter() {
+                if let Some(key) = key {
+                    references(key, params, refs);
+                }
+                references(value, params, refs);
+            }
+        }
+        _ => {}
+    }
+}
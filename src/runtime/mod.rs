@@ -1,33 +1,202 @@
-pub mod func;
-pub mod overload;
+pub mod builtin;
+pub mod convert;
+pub mod memo;
+pub mod native;
+pub mod pretty;
 
-pub use self::func::*;
-pub use self::overload::*;
+pub use self::native::{NativeFn, NativeResult};
+pub use self::pretty::{Format, Pretty};
+pub use localc_cc_lib::function::*;
+pub use localc_cc_lib::overload::*;
 
 use super::*;
 
 use lovm::*;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+
+const DIFF_USAGE: &str = "usage: diff(function, variable)";
+
+// error of executions stopped through the flag passed to `Runtime::set_interrupt`
+pub const INTERRUPTED: &str = "execution interrupted";
 
 pub struct Runtime {
     fn_templates: HashMap<Name, Function>,
     pub unit: gen::UnitBuilder,
     pub(crate) vm: vm::Vm,
+    // dispatch overloads with many constant arguments through jump tables
+    pub jump_tables: bool,
+    // ids of natives in the global registry that are owned by this runtime
+    natives: Vec<usize>,
+    // immutable constants and pure functions known at compile time
+    constants: optimize::FoldContext,
+    // cached results of memoized functions
+    memo: memo::MemoCache,
+    // ids of the natives each function is bound to
+    bindings: HashMap<Name, Vec<usize>>,
+    // functions whose code objects changed since the unit was last synchronized
+    pending: HashSet<Name>,
+    // derivatives defined by `differentiate`. other functions are never overwritten by it.
+    derivatives: HashSet<Name>,
+    // precompiled units loaded after the runtimes own unit
+    units: Vec<lovm::Unit>,
+    // native called by every function before it dispatches, see `set_interrupt`
+    interrupt: Option<usize>,
 }
 
 impl Runtime {
     pub fn new() -> Self {
-        Self {
+        let mut runtime = Self {
             fn_templates: HashMap::new(),
             unit: gen::UnitBuilder::new(),
             vm: vm::Vm::new(),
-        }
+            jump_tables: true,
+            natives: vec![],
+            constants: optimize::FoldContext::new(),
+            memo: memo::MemoCache::default(),
+            bindings: HashMap::new(),
+            pending: HashSet::new(),
+            derivatives: HashSet::new(),
+            units: vec![],
+            interrupt: None,
+        };
+
+        runtime
+            .vm
+            .interrupts_mut()
+            .set(native::NATIVE_INTERRUPT, &native::dispatch);
+        builtin::install(&mut runtime);
+        runtime.sync().expect("loading builtins failed");
+
+        runtime
+    }
+
+    // add a rust closure as overload of `name` taking `arity` arguments. natives that are not
+    // `pure` keep functions calling them from being memoized.
+    pub(crate) fn define_native(
+        &mut self,
+        name: &Name,
+        arity: usize,
+        native: NativeFn,
+        pure: bool,
+    ) {
+        let id = self.bind(name, native, pure);
+
+        let (overload, body) = native::overload_body(id, arity);
+        let fn_template = self
+            .fn_templates
+            .entry(name.clone())
+            .or_insert_with(Function::new);
+        fn_template.overload(overload, body);
+
+        self.pending.insert(name.clone());
+    }
+
+    // add a rust closure that is called with the arguments of any call to `name` that no
+    // overload accepts
+    pub(crate) fn define_variadic(&mut self, name: &Name, native: NativeFn, pure: bool) {
+        let id = self.bind(name, native, pure);
+
+        self.fn_templates
+            .entry(name.clone())
+            .or_insert_with(Function::new)
+            .variadic(id);
+
+        self.pending.insert(name.clone());
+    }
+
+    fn bind(&mut self, name: &Name, native: NativeFn, pure: bool) -> usize {
+        let id = native::register(native, pure);
+        self.natives.push(id);
+        self.bindings
+            .entry(name.clone())
+            .or_insert_with(Vec::new)
+            .push(id);
+        id
+    }
+
+    // stop running code with `INTERRUPTED` as soon as `flag` is set. the flag is checked on
+    // every function call, so every loop can be interrupted. the flag has to be cleared by
+    // the caller.
+    pub fn set_interrupt(&mut self, flag: std::sync::Arc<std::sync::atomic::AtomicBool>) {
+        let check = move |_: &mut vm::VmData, _: &[lovm::Value]| {
+            if flag.load(std::sync::atomic::Ordering::SeqCst) {
+                Err(INTERRUPTED.to_string())
+            } else {
+                Ok(lovm::Value::T(false))
+            }
+        };
+        let id = native::register(std::sync::Arc::new(check), false);
+        self.natives.push(id);
+        self.interrupt = Some(id);
+        // every function has to be rebuilt with the check
+        self.pending = self.fn_templates.keys().cloned().collect();
+    }
+
+    // make the rust function `native` callable as `name` with `arity` arguments. the native
+    // becomes an overload of `name`: localc overloads with constant arguments are tried before
+    // it and a localc overload of the same arity taking only variables replaces it.
+    pub fn register_native<F>(&mut self, name: &str, arity: usize, native: F)
+    where
+        F: Fn(&[lovm::Value]) -> Result<lovm::Value, String> + Send + Sync + 'static,
+    {
+        let name = name.to_string();
+        let native = move |_: &mut vm::VmData, args: &[lovm::Value]| native(args);
+        self.constants.forget_function(&name);
+        // the host may return different results for the same arguments
+        self.define_native(&name, arity, std::sync::Arc::new(native), false);
+        self.invalidate(&name);
+    }
+
+    // generate the code object of `name`
+    fn build_fun(&self, name: &Name) -> CodeObject {
+        let tables = if self.jump_tables {
+            Some(JumpTables {
+                threshold: JUMP_TABLE_THRESHOLD,
+                is_number: native::is_number(),
+            })
+        } else {
+            None
+        };
+        self.fn_templates[name]
+            .build_with(tables, self.interrupt)
+            .unwrap()
+    }
+
+    // add a pure numeric function that is also evaluated at compile time
+    pub(crate) fn define_pure(&mut self, name: &Name, arity: usize, func: optimize::PureFn) {
+        self.define_native(name, arity, pure_native(func), true);
+        self.constants.function(name, arity, func);
+    }
+
+    // like `define_pure` for a function taking any number of arguments
+    pub(crate) fn define_pure_variadic(&mut self, name: &Name, func: optimize::PureFn) {
+        self.define_variadic(name, pure_native(func), true);
+        self.constants.variadic(name, func);
+    }
+
+    // globals defined as constant cannot be reassigned and are inlined by the compiler
+    pub fn define_const(&mut self, name: &Name, value: ast::Value) -> Result<(), String> {
+        let converted = lovm::Value::try_from(value.clone())?;
+        self.vm.data.globals.insert(name.clone(), converted);
+        self.constants.constant(name, value);
+        Ok(())
     }
 
     pub fn store_var(&mut self, name: &Name, expr: &Expr) -> ReplResult {
-        let value = self.run_expr(expr)?.unwrap();
+        if self.constants.is_constant(name) {
+            return Err(format!(
+                "cannot assign to constant `{}`, choose another name",
+                name
+            ));
+        }
+        let expr = self.define_derivatives(expr)?;
+        let value = self
+            .run_expr(&expr)?
+            .ok_or_else(|| format!("cannot assign to `{}`, the expression has no value", name))?;
         self.vm.data.globals.insert(name.clone(), value);
+        self.invalidate(name);
         Ok(None)
     }
 
@@ -36,35 +205,289 @@ impl Runtime {
             self.fn_templates.insert(name.clone(), Function::new());
         }
 
-        let overload_co = compiler::compile_with_params_lazy(expr, params)?;
+        self.constants.forget_function(name);
+        let derived = self.define_derivatives(expr)?;
+        let lowered = self.define_callbacks(&derived, params)?;
+        let folded = optimize::fold_with_params(&lowered, &self.constants, params);
+        let body = builtin::linalg::lower(&folded, &|name| self.constants.is_pure(name));
+        let overload_co = compiler::compile_overload(&body, params, name)?;
         let fn_template = self.fn_templates.get_mut(name).unwrap();
         fn_template.overload(params.clone(), overload_co);
-        let co = fn_template.build().unwrap();
+        fn_template.define(params.clone(), expr.clone());
+
+        // the code object is built lazily before the next execution. this way a function
+        // is compiled only once, even if many overloads are defined in a row.
+        self.pending.insert(name.clone());
+        self.invalidate(name);
+
+        Ok(None)
+    }
+
+    // lower calls of `map`, `filter` and `fold` in `expr` and define the helpers they call.
+    // helpers call their callback by name, so callbacks cannot be parameters in `params`.
+    fn define_callbacks(&mut self, expr: &Expr, params: &[Expr]) -> Result<Expr, String> {
+        let mut found = vec![];
+        let lowered = builtin::collection::lower(expr, &mut found);
+        for (kind, callback) in found.iter() {
+            if params.contains(&Expr::Ref(callback.clone())) {
+                return Err(format!(
+                    "callback of `{}` must be the name of a function, `{}` is a parameter",
+                    kind, callback
+                ));
+            }
+            let name = builtin::collection::helper_name(kind, callback);
+            if self.fn_templates.contains_key(&name) {
+                continue;
+            }
+            for (params, body) in builtin::collection::helper(kind, callback).iter() {
+                self.store_fun(&name, params, body)?;
+            }
+        }
+        Ok(lowered)
+    }
+
+    // names of all defined functions in alphabetical order. helpers generated for callbacks
+    // are left out.
+    pub fn functions(&self) -> Vec<&Name> {
+        let mut names = self
+            .fn_templates
+            .keys()
+            .filter(|name| !builtin::collection::is_hidden(name))
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    pub fn function(&self, name: &str) -> Option<&Function> {
+        self.fn_templates.get(name)
+    }
 
-        self.unit.set(name, co);
+    // names and values of all globals in alphabetical order
+    pub fn globals(&self) -> Vec<(&Name, &lovm::Value)> {
+        let mut globals = self.vm.data.globals.iter().collect::<Vec<_>>();
+        globals.sort_by_key(|(name, _)| name.as_str());
+        globals
+    }
 
-        let unit = self.unit.build().unwrap();
-        match self.vm.data.units.0.get_mut(0) {
-            Some(slot) => *slot = unit,
-            _ => self.vm.data.units.load(&unit)?,
+    // remove the function or global `name`. the unit is rebuilt from scratch because lovm
+    // does not support removing code objects from a loaded unit.
+    pub fn undefine(&mut self, name: &Name) -> Result<(), String> {
+        let was_global = self.vm.data.globals.remove(name).is_some();
+        let was_function = self.fn_templates.remove(name).is_some();
+        if !was_global && !was_function {
+            return Err(format!("`{}` is not defined", name));
         }
 
-        Ok(None)
+        self.constants.forget(name);
+        self.bindings.remove(name);
+        self.invalidate(name);
+        self.memo.lock().unwrap().remove(name);
+
+        if was_function {
+            self.unit = gen::UnitBuilder::new();
+            self.vm.data.units.0.clear();
+            self.pending = self.fn_templates.keys().cloned().collect();
+        }
+
+        Ok(())
+    }
+
+    // listing of the code object of `name` including its overload dispatch
+    pub fn disassemble(&self, name: &str) -> Option<String> {
+        if !self.fn_templates.contains_key(name) {
+            return None;
+        }
+        Some(disasm::disassemble(&self.build_fun(&name.to_string())))
+    }
+
+    // compile an expression the same way it would be executed by `run_expr`
+    pub fn compile(&self, expr: &Expr) -> Result<CodeObject, String> {
+        let expr = builtin::collection::lower(expr, &mut vec![]);
+        let expr = optimize::fold(&expr, &self.constants);
+        let expr = builtin::linalg::lower(&expr, &|name| self.constants.is_pure(name));
+        compiler::compile_expr(&expr)
+    }
+
+    // rebuild the code objects of all changed functions and patch them into the loaded unit.
+    // the whole unit is only built if it was not loaded before.
+    pub fn sync(&mut self) -> Result<(), String> {
+        let pending = self.pending.drain().collect::<Vec<_>>();
+
+        for name in pending.iter() {
+            let co = self.build_fun(name);
+            if let Some(loaded) = self.vm.data.units.0.get_mut(0) {
+                loaded.set(name, co.clone());
+            }
+            self.unit.set(name, co);
+        }
+
+        if self.vm.data.units.0.is_empty() {
+            let unit = self.unit.build().unwrap();
+            self.vm.data.units.load(&unit)?;
+            for unit in self.units.iter() {
+                self.vm.data.units.load(unit)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // make the functions of a precompiled unit callable. functions of the runtime take
+    // precedence over those of the unit.
+    pub fn load_unit(&mut self, unit: lovm::Unit) -> Result<(), String> {
+        self.sync()?;
+        self.vm.data.units.load(&unit)?;
+        self.units.push(unit);
+        Ok(())
+    }
+
+    // differentiate every overload of `name` that takes `var` as parameter and store the
+    // results as function `name'`. returns the name of the derivative.
+    pub fn differentiate(&mut self, name: &Name, var: &str) -> Result<Name, String> {
+        let definitions = match self.fn_templates.get(name) {
+            Some(fn_template) => fn_template
+                .definitions()
+                .filter(|(overload, _)| {
+                    overload
+                        .iter()
+                        .any(|param| *param == Expr::Ref(var.to_string()))
+                })
+                .map(|(overload, expr)| {
+                    (overload.iter().cloned().collect::<Vec<_>>(), expr.clone())
+                })
+                .collect::<Vec<_>>(),
+            _ => return Err(format!("function `{}` is not defined", name)),
+        };
+
+        if definitions.is_empty() {
+            return Err(format!(
+                "`{}` has no overload with parameter `{}`",
+                name, var
+            ));
+        }
+
+        let derived = format!("{}'", name);
+        if self.fn_templates.contains_key(&derived) && !self.derivatives.contains(&derived) {
+            return Err(format!(
+                "cannot store the derivative of `{}`, `{}` is already defined",
+                name, derived
+            ));
+        }
+        self.derivatives.insert(derived.clone());
+        for (params, expr) in definitions.iter() {
+            let expr = diff::diff_with(expr, var, &|name, argc| self.inline_candidate(name, argc))?;
+            let expr = simplify::simplify(&expr);
+            self.store_fun(&derived, params, &expr)?;
+        }
+
+        Ok(derived)
+    }
+
+    // differentiate for every `diff(f, x)` nested in `expr` and replace the call with the name
+    // of the derivative. a user-defined `diff` is called like any other function.
+    fn define_derivatives(&mut self, expr: &Expr) -> Result<Expr, String> {
+        if self.fn_templates.contains_key("diff") {
+            return Ok(expr.clone());
+        }
+        let derived = match expr {
+            Expr::Func(name, args) if name == "diff" => match args.as_slice() {
+                [Expr::Ref(fname), Expr::Ref(var)] => {
+                    let derived = self.differentiate(fname, var)?;
+                    Expr::Value(ast::Value::Str(derived.as_str().into()))
+                }
+                _ => return Err(DIFF_USAGE.to_string()),
+            },
+            Expr::Comp(op, lhs, rhs) => Expr::Comp(
+                op.clone(),
+                Box::new(self.define_derivatives(lhs)?),
+                Box::new(self.define_derivatives(rhs)?),
+            ),
+            Expr::Func(name, args) => Expr::Func(
+                name.clone(),
+                args.iter()
+                    .map(|arg| self.define_derivatives(arg))
+                    .collect::<Result<_, _>>()?,
+            ),
+            Expr::Value(ast::Value::Tuple(items)) => Expr::Value(ast::Value::Tuple(
+                items
+                    .iter()
+                    .map(|item| self.define_derivatives(item))
+                    .collect::<Result<_, _>>()?,
+            )),
+            other => other.clone(),
+        };
+        Ok(derived)
+    }
+
+    // returns parameters and body of `name` if it has exactly one overload with `argc`
+    // arguments and that overload only takes variables.
+    fn inline_candidate(&self, name: &str, argc: usize) -> Option<(Vec<String>, Expr)> {
+        let mut candidates = self
+            .fn_templates
+            .get(name)?
+            .definitions()
+            .filter(|(overload, _)| overload.count() == argc);
+        let (overload, expr) = candidates.next()?;
+        if candidates.next().is_some() || !overload.accepts_count(argc) {
+            return None;
+        }
+        let params = overload
+            .iter()
+            .filter_map(|param| match param {
+                Expr::Ref(name) => Some(name.clone()),
+                _ => None,
+            })
+            .collect();
+        Some((params, expr.clone()))
     }
 
     pub fn run_expr(&mut self, expr: &Expr) -> ReplResult {
         match expr {
+            Expr::Func(name, args) if name == "diff" && !self.fn_templates.contains_key(name) => {
+                match args.as_slice() {
+                    [Expr::Ref(fname), Expr::Ref(var)] => {
+                        self.differentiate(fname, var)?;
+                        Ok(None)
+                    }
+                    _ => Err(DIFF_USAGE.to_string()),
+                }
+            }
+            Expr::Func(name, args) if name == "memo" && !self.fn_templates.contains_key(name) => {
+                match args.as_slice() {
+                    [Expr::Ref(fname)] => {
+                        self.memoize(fname)?;
+                        Ok(None)
+                    }
+                    _ => Err("usage: memo(function)".to_string()),
+                }
+            }
+            Expr::Func(name, args)
+                if name == "simplify" && !self.fn_templates.contains_key(name) =>
+            {
+                match args.as_slice() {
+                    [arg] => {
+                        let result = simplify::simplify(arg).to_string();
+                        Ok(Some(lovm::Value::Str(result.as_str().into())))
+                    }
+                    _ => Err("usage: simplify(expression)".to_string()),
+                }
+            }
             Expr::Comp(Operator::Store, lhs, rhs) => match lhs {
-                box Expr::Func(name, params) => self.store_fun(name, params, rhs),
+                box Expr::Func(name, params) => {
+                    self.derivatives.remove(name);
+                    self.store_fun(name, params, rhs)
+                }
                 box Expr::Ref(name) => self.store_var(name, rhs),
                 _ => Err("assignment not allowed".to_string()),
             },
             _ => {
+                let expr = self.define_derivatives(expr)?;
+                self.define_callbacks(&expr, &[])?;
                 // TODO: if this returns a reference to an temporary object; drop it to save memory
-                let co = compiler::compile_expr(expr)?;
+                let co = self.compile(&expr)?;
 
-                if cfg!(debug_assertions) {
-                    println!("{:?}", co);
+                if log::log_enabled!(log::Level::Debug) {
+                    log::debug!("{}", disasm::disassemble(&co));
                 }
 
                 // no storage location given: execute directly
@@ -75,6 +498,8 @@ impl Runtime {
 
     // thin wrapper for repl
     pub fn run(&mut self, co: CodeObject) -> ReplResult {
+        self.sync()?;
+
         let co = co.into_ref();
 
         self.vm.data.state = vm::VmState::Running;
@@ -88,3 +513,29 @@ impl Runtime {
         Ok(result)
     }
 }
+
+// wrap `func` into a native that converts its arguments to numbers
+fn pure_native(func: optimize::PureFn) -> NativeFn {
+    std::sync::Arc::new(move |_: &mut vm::VmData, args: &[lovm::Value]| {
+        let args = args
+            .iter()
+            .map(|arg| match arg {
+                lovm::Value::I64(n) => Ok(NumType::Natural(*n)),
+                lovm::Value::F64(n) => Ok(NumType::Rational(*n)),
+                other => Err(format!("expected number, got `{:?}`", other)),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        match func(&args) {
+            Some(result) => Ok(result.into()),
+            _ => Err("argument out of domain".to_string()),
+        }
+    })
+}
+
+impl Drop for Runtime {
+    fn drop(&mut self) {
+        for id in self.natives.iter() {
+            native::unregister(*id);
+        }
+    }
+}
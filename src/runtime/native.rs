@@ -0,0 +1,164 @@
+use super::*;
+
+use lovm::gen::*;
+use lovm::vm::object::*;
+
+use std::sync::{Arc, RwLock};
+
+// native functions are rust closures that can be called from localc code. lovm does not know
+// about them: a native is wrapped into a tiny code object that pushes its arguments, the
+// argument count and the natives id before raising `NATIVE_INTERRUPT`. the interrupt handler
+// looks up the closure in a process wide registry and pushes its result onto the stack.
+
+pub use localc_cc_lib::function::{call_native as call, NATIVE_INTERRUPT};
+
+pub type NativeResult = Result<lovm::Value, String>;
+pub type NativeFn = Arc<dyn Fn(&mut vm::VmData, &[lovm::Value]) -> NativeResult + Send + Sync>;
+
+lazy_static::lazy_static! {
+    // every native is stored with a flag telling whether it is pure
+    static ref NATIVES: RwLock<Vec<Option<(NativeFn, bool)>>> = RwLock::new(vec![]);
+    // shared by all runtimes and never unregistered
+    static ref IS_NUMBER: usize = register(
+        Arc::new(|_, args| match args {
+            [lovm::Value::I64(_)] | [lovm::Value::F64(_)] => Ok(lovm::Value::T(true)),
+            _ => Ok(lovm::Value::T(false)),
+        }),
+        true,
+    );
+}
+
+// add a closure to the registry and return its id. slots of dropped runtimes are reused.
+// `pure` natives have no side effects and return the same result for the same arguments.
+pub fn register(native: NativeFn, pure: bool) -> usize {
+    let mut natives = NATIVES.write().unwrap();
+    match natives.iter().position(Option::is_none) {
+        Some(id) => {
+            natives[id] = Some((native, pure));
+            id
+        }
+        _ => {
+            natives.push(Some((native, pure)));
+            natives.len() - 1
+        }
+    }
+}
+
+pub fn is_pure(id: usize) -> bool {
+    match NATIVES.read().unwrap().get(id) {
+        Some(Some((_, pure))) => *pure,
+        _ => false,
+    }
+}
+
+pub fn unregister(id: usize) {
+    if let Some(slot) = NATIVES.write().unwrap().get_mut(id) {
+        *slot = None;
+    }
+}
+
+// interrupt handler: expects `args..., argc, id` on top of the value stack
+pub fn dispatch(data: &mut vm::VmData) -> vm::VmResult {
+    let id = pop_count(data)?;
+    let argc = pop_count(data)?;
+
+    if data.vstack.len() < argc {
+        return Err(format!("native call expected {} arguments on stack", argc));
+    }
+    let args = data.vstack.split_off(data.vstack.len() - argc);
+
+    let native = match NATIVES.read().unwrap().get(id) {
+        Some(Some((native, _))) => native.clone(),
+        _ => return Err(format!("no native function with id `{}`", id)),
+    };
+
+    let result = native(data, &args)?;
+    data.vstack.push(result);
+
+    Ok(())
+}
+
+fn pop_count(data: &mut vm::VmData) -> Result<usize, String> {
+    match data.vstack.pop() {
+        Some(lovm::Value::I64(n)) if 0 <= n => Ok(n as usize),
+        other => Err(format!("expected count on stack, got `{:?}`", other)),
+    }
+}
+
+// generate the body of an overload that forwards `arity` arguments to native `id`. the
+// parameters are named like the default arguments of a vtable (`arg0, arg1, ... argn`).
+pub fn overload_body(id: usize, arity: usize) -> (Overload, CodeBuilder) {
+    let params = (0..arity).map(|i| format!("arg{}", i)).collect::<Vec<_>>();
+    let overload = Overload::from(
+        params
+            .iter()
+            .map(|param| Expr::Ref(param.clone()))
+            .collect::<Vec<_>>(),
+    );
+
+    let mut body = CodeBuilder::new().with_params(params.clone());
+    call(&mut body, id, &params, 0);
+
+    (overload, body)
+}
+
+// id of the native that tests if its argument is a number. used by jump tables.
+pub fn is_number() -> usize {
+    *IS_NUMBER
+}
+
+// helpers for accessing objects behind `Value::Ref`
+
+pub fn array(data: &vm::VmData, value: &lovm::Value) -> Result<Vec<lovm::Value>, String> {
+    array_ref(data, value).map(|items| items.clone())
+}
+
+// borrow the items of a tuple without copying them
+pub fn array_ref<'a>(
+    data: &'a vm::VmData,
+    value: &lovm::Value,
+) -> Result<&'a Vec<lovm::Value>, String> {
+    match value {
+        lovm::Value::Ref(handle) => match data.obj_pool.get(handle).map(|obj| &obj.inner) {
+            Some(ObjectKind::Array(array)) => Ok(array.inner()),
+            Some(other) => Err(format!("expected tuple, got `{:?}`", other)),
+            _ => Err(format!("invalid reference `{}`", handle)),
+        },
+        other => Err(format!("expected tuple, got `{:?}`", other)),
+    }
+}
+
+pub fn dict(
+    data: &vm::VmData,
+    value: &lovm::Value,
+) -> Result<std::collections::HashMap<lovm::Value, lovm::Value>, String> {
+    match value {
+        lovm::Value::Ref(handle) => match data.obj_pool.get(handle).map(|obj| &obj.inner) {
+            Some(ObjectKind::Dict(dict)) => Ok(dict.inner().clone()),
+            Some(other) => Err(format!("expected set, got `{:?}`", other)),
+            _ => Err(format!("invalid reference `{}`", handle)),
+        },
+        other => Err(format!("expected set, got `{:?}`", other)),
+    }
+}
+
+pub fn alloc_array(data: &mut vm::VmData, items: Vec<lovm::Value>) -> lovm::Value {
+    let handle = data.obj_pool.store(ObjectKind::Array(items.into()));
+    lovm::Value::Ref(handle)
+}
+
+pub fn alloc_dict(data: &mut vm::VmData, entries: Vec<(lovm::Value, lovm::Value)>) -> lovm::Value {
+    let dict = entries
+        .into_iter()
+        .collect::<std::collections::HashMap<_, _>>();
+    let handle = data.obj_pool.store(ObjectKind::Dict(dict.into()));
+    lovm::Value::Ref(handle)
+}
+
+pub fn number(value: &lovm::Value) -> Result<f64, String> {
+    match value {
+        lovm::Value::I64(n) => Ok(*n as f64),
+        lovm::Value::F64(n) => Ok(*n),
+        other => Err(format!("expected number, got `{:?}`", other)),
+    }
+}
@@ -0,0 +1,279 @@
+use super::*;
+
+use lovm::vm::object::*;
+
+use std::fmt::Write;
+
+// render vm values in localc syntax. tuples and sets are printed recursively; an object that
+// contains itself is printed as `...` on the second visit.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    // localc syntax
+    Plain,
+    // tuples become arrays and sets with string keys become objects. other sets become
+    // arrays of `[key, value]` pairs. nothing is truncated or rounded; objects that contain
+    // themselves are written as `null`.
+    Json,
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "plain" => Ok(Format::Plain),
+            "json" => Ok(Format::Json),
+            _ => Err(format!(
+                "unknown format `{}`, expected `plain` or `json`",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Pretty {
+    pub format: Format,
+    // digits after the decimal point. `None` prints the shortest exact representation.
+    pub precision: Option<usize>,
+    // items of a tuple or set that are printed before the rest is elided
+    pub max_items: usize,
+}
+
+impl Default for Pretty {
+    fn default() -> Self {
+        Self {
+            format: Format::Plain,
+            precision: None,
+            max_items: 32,
+        }
+    }
+}
+
+impl Pretty {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_precision(mut self, precision: usize) -> Self {
+        self.precision = Some(precision);
+        self
+    }
+
+    pub fn with_max_items(mut self, max_items: usize) -> Self {
+        self.max_items = max_items;
+        self
+    }
+
+    pub fn with_format(mut self, format: Format) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn format(&self, data: &vm::VmData, value: &lovm::Value) -> String {
+        let mut out = String::new();
+        match self.format {
+            Format::Plain => self.write(&mut out, data, value, &mut vec![]),
+            Format::Json => self.write_json(&mut out, data, value, &mut vec![]),
+        }
+        out
+    }
+
+    pub fn float(&self, n: f64) -> String {
+        if !n.is_finite() {
+            return ast::non_finite(n).to_string();
+        }
+        let mut repr = match self.precision {
+            Some(precision) => {
+                let repr = format!("{:.*}", precision, n);
+                if repr.contains('.') {
+                    repr.trim_end_matches('0').to_string()
+                } else {
+                    repr
+                }
+            }
+            // `Display` never uses an exponent which localc could not parse back
+            _ => format!("{}", n),
+        };
+        // floats always keep their decimal point to distinguish them from integers
+        if repr.ends_with('.') {
+            repr.push('0');
+        } else if !repr.contains('.') {
+            repr.push_str(".0");
+        }
+        repr
+    }
+
+    fn write(
+        &self,
+        out: &mut String,
+        data: &vm::VmData,
+        value: &lovm::Value,
+        visited: &mut Vec<usize>,
+    ) {
+        match value {
+            lovm::Value::I64(n) => write!(out, "{}", n).unwrap(),
+            lovm::Value::F64(n) => out.push_str(&self.float(*n)),
+            lovm::Value::T(t) => write!(out, "{}", t).unwrap(),
+            // quotes are doubled like in `ast::Value`s display
+            lovm::Value::Str(s) => write!(out, "'{}'", s.to_string().replace('\'', "''")).unwrap(),
+            lovm::Value::Ref(handle) if visited.contains(handle) => out.push_str("..."),
+            lovm::Value::Ref(handle) => {
+                visited.push(*handle);
+                match data.obj_pool.get(handle).map(|obj| &obj.inner) {
+                    Some(ObjectKind::Array(array)) => {
+                        let items = array.inner();
+                        self.write_items(
+                            out,
+                            "(",
+                            ")",
+                            items.len(),
+                            items.iter(),
+                            |out, item, visited| self.write(out, data, item, visited),
+                            visited,
+                        );
+                    }
+                    Some(ObjectKind::Dict(dict)) => {
+                        let dict = dict.inner();
+                        let mut entries = dict.iter().collect::<Vec<_>>();
+                        // dicts are unordered; print sorted by key for stable output
+                        entries.sort_by(|(a, _), (b, _)| convert::cmp_keys(a, b));
+                        self.write_items(
+                            out,
+                            "{",
+                            "}",
+                            entries.len(),
+                            entries.into_iter(),
+                            |out, (key, value), visited| {
+                                self.write(out, data, key, visited);
+                                out.push_str(" = ");
+                                self.write(out, data, value, visited);
+                            },
+                            visited,
+                        );
+                    }
+                    Some(other) => write!(out, "{:?}", other).unwrap(),
+                    _ => write!(out, "<invalid reference {}>", handle).unwrap(),
+                }
+                visited.pop();
+            }
+            other => write!(out, "{:?}", other).unwrap(),
+        }
+    }
+
+    fn write_json(
+        &self,
+        out: &mut String,
+        data: &vm::VmData,
+        value: &lovm::Value,
+        visited: &mut Vec<usize>,
+    ) {
+        match value {
+            lovm::Value::I64(n) => write!(out, "{}", n).unwrap(),
+            lovm::Value::F64(n) if n.is_finite() => write!(out, "{:?}", n).unwrap(),
+            lovm::Value::T(t) => write!(out, "{}", t).unwrap(),
+            lovm::Value::Str(s) => json_str(out, &s.to_string()),
+            lovm::Value::Ref(handle) if !visited.contains(handle) => {
+                visited.push(*handle);
+                match data.obj_pool.get(handle).map(|obj| &obj.inner) {
+                    Some(ObjectKind::Array(array)) => {
+                        out.push('[');
+                        for (i, item) in array.inner().iter().enumerate() {
+                            if 0 < i {
+                                out.push_str(", ");
+                            }
+                            self.write_json(out, data, item, visited);
+                        }
+                        out.push(']');
+                    }
+                    Some(ObjectKind::Dict(dict)) => {
+                        let mut entries = dict.inner().iter().collect::<Vec<_>>();
+                        entries.sort_by(|(a, _), (b, _)| convert::cmp_keys(a, b));
+                        let object = entries.iter().all(|(key, _)| match key {
+                            lovm::Value::Str(_) => true,
+                            _ => false,
+                        });
+                        out.push(if object { '{' } else { '[' });
+                        for (i, (key, value)) in entries.into_iter().enumerate() {
+                            if 0 < i {
+                                out.push_str(", ");
+                            }
+                            match key {
+                                lovm::Value::Str(s) if object => {
+                                    json_str(out, &s.to_string());
+                                    out.push_str(": ");
+                                    self.write_json(out, data, value, visited);
+                                }
+                                _ => {
+                                    out.push('[');
+                                    self.write_json(out, data, key, visited);
+                                    out.push_str(", ");
+                                    self.write_json(out, data, value, visited);
+                                    out.push(']');
+                                }
+                            }
+                        }
+                        out.push(if object { '}' } else { ']' });
+                    }
+                    _ => out.push_str("null"),
+                }
+                visited.pop();
+            }
+            _ => out.push_str("null"),
+        }
+    }
+
+    fn write_items<I, T, F>(
+        &self,
+        out: &mut String,
+        open: &str,
+        close: &str,
+        len: usize,
+        items: I,
+        write_item: F,
+        visited: &mut Vec<usize>,
+    ) where
+        I: Iterator<Item = T>,
+        F: Fn(&mut String, T, &mut Vec<usize>),
+    {
+        out.push_str(open);
+        for (i, item) in items.take(self.max_items).enumerate() {
+            if 0 < i {
+                out.push_str(", ");
+            }
+            write_item(out, item, visited);
+        }
+        if self.max_items < len {
+            if 0 < self.max_items {
+                out.push_str(", ");
+            }
+            write!(out, "... {} more", len - self.max_items).unwrap();
+        }
+        out.push_str(close);
+    }
+}
+
+// write `s` as quoted json string
+pub fn json_str(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+impl Runtime {
+    // render `value` according to `pretty`
+    pub fn pretty(&self, value: &lovm::Value, pretty: &Pretty) -> String {
+        pretty.format(&self.vm.data, value)
+    }
+}
@@ -229,6 +229,1277 @@ fn sets() {
     }
 }
 
+#[test]
+fn linalg() {
+    use crate::runtime::native;
+
+    let mut repl = Repl::new();
+
+    expect!(repl, "dot((1, 2, 3), (4, 5, 6))", lovm::Value::I64(32));
+    expect!(repl, "det(((1, 2), (3, 4)))", lovm::Value::F64(-2.));
+
+    repl.run("a = ((2, 1), (1, 3))").unwrap();
+    repl.run("x = lsolve(a, (3, 5))").unwrap();
+    let result = repl.run("a * x").unwrap().unwrap();
+    let items = native::array(&repl.runtime.vm.data, &result).unwrap();
+    assert_eq!(items.len(), 2);
+    // the solution is only exact up to rounding
+    for (item, expected) in items.iter().zip([3., 5.].iter()) {
+        let n = native::number(item).unwrap();
+        assert!((n - expected).abs() < 1e-9, "{} != {}", n, expected);
+    }
+
+    // element-wise operators also apply in function bodies
+    repl.run("f(v) = v + (1, 2)").unwrap();
+    let result = repl.run("f((3, 4))").unwrap().unwrap();
+    assert_eq!(
+        native::array(&repl.runtime.vm.data, &result).unwrap(),
+        vec![lovm::Value::I64(4), lovm::Value::I64(6)]
+    );
+
+    // whether operators combine tuples is decided at runtime
+    let show = |repl: &mut Repl, line: &str| {
+        let result = repl.run(line).unwrap().unwrap();
+        repl.format(&result)
+    };
+    repl.run("g(v) = v + v").unwrap();
+    assert_eq!(show(&mut repl, "g((1, 2))"), "(2, 4)");
+    expect!(repl, "g(3)", lovm::Value::I64(6));
+    repl.run("h(n) = (n, n)").unwrap();
+    assert_eq!(show(&mut repl, "h(1) + h(2)"), "(3, 3)");
+    repl.run("k = 1").unwrap();
+    repl.run("m(v) = v * k").unwrap();
+    repl.run("k = (1, 2)").unwrap();
+    expect!(repl, "m((3, 4))", lovm::Value::I64(11));
+    repl.run("cat(s) = s + 'b'").unwrap();
+    expect!(repl, "cat('a')", lovm::Value::Str("ab".into()));
+    repl.run("vadd(a, b) = 0").unwrap();
+    assert_eq!(show(&mut repl, "(1, 2) + (3, 4)"), "(4, 6)");
+
+    // the singularity test is relative to the size of the entries
+    assert!(repl
+        .run("inv(((0.0000000000001, 0), (0, 0.0000000000001)))")
+        .is_ok());
+    assert!(repl.run("inv(((1, 2), (2, 4)))").is_err());
+}
+
+#[test]
+fn differentiate() {
+    let mut repl = Repl::new();
+
+    repl.run("f(x) = x ^ 3 + 2 * x").unwrap();
+    repl.run("g(x) = f(x) * 2").unwrap();
+    repl.run("diff(f, x)").unwrap();
+    repl.run("diff(g, x)").unwrap();
+
+    expect!(repl, "f'(2)", lovm::Value::I64(14));
+    expect!(repl, "g'(2)", lovm::Value::I64(28));
+
+    assert!(repl.run("diff(f, y)").is_err());
+
+    // derivatives divide without truncating
+    repl.run("h(x) = x / 2 + ln(x)").unwrap();
+    repl.run("diff(h, x)").unwrap();
+    expect!(repl, "h'(2)", lovm::Value::F64(1.));
+
+    repl.run("fac(x) = x * fac(x - 1)").unwrap();
+    assert!(repl.run("diff(fac, x)").is_err());
+
+    // nested calls evaluate to the name of the derivative
+    repl.run("k(x) = x ^ 2").unwrap();
+    repl.run("d = diff(k, x)").unwrap();
+    expect!(repl, "d", lovm::Value::Str("k'".into()));
+    expect!(repl, "k'(3)", lovm::Value::I64(6));
+
+    // derivatives are updated, but functions defined by the user are kept
+    repl.run("k(x) = x ^ 3").unwrap();
+    repl.run("diff(k, x)").unwrap();
+    expect!(repl, "k'(1)", lovm::Value::I64(3));
+    repl.run("even(x) = x % 2 == 0").unwrap();
+    repl.run("even'(x) = even(x) == false").unwrap();
+    assert!(repl.run("diff(even, x)").is_err());
+    expect!(repl, "even'(1)", lovm::Value::T(true));
+    repl.run("k'(x) = 0").unwrap();
+    assert!(repl.run("diff(k, x)").is_err());
+
+    // a user-defined `diff` is not intercepted
+    repl.run("diff(a, b) = a - b").unwrap();
+    expect!(repl, "diff(5, 3)", lovm::Value::I64(2));
+}
+
+#[test]
+fn simplify() {
+    let parser = ExprParser::new();
+    let simplified = |raw| simplify::simplify(&parser.parse(raw).unwrap());
+    let parsed = |raw| parser.parse(raw).unwrap();
+
+    assert_eq!(simplified("x * 1 + 0"), parsed("x"));
+    assert_eq!(simplified("2 * x + 3 * x"), parsed("5 * x"));
+    assert_eq!(simplified("x * y / x"), parsed("y"));
+    assert_eq!(simplified("x - x"), parsed("0"));
+    assert_eq!(simplified("-1 * (p / 2)"), parsed("-1 * p / 2"));
+    assert_eq!(simplified("x ^ 2 * x"), parsed("x ^ 3"));
+    assert_eq!(simplified("x / y"), parsed("x / y"));
+    assert_eq!(simplified("3 / 6"), parsed("1 / 2"));
+    assert_eq!(simplified("7 / 2"), parsed("7 / 2"));
+    assert_eq!(simplified("1.0 * x / y"), parsed("1.0 * x / y"));
+    // merging the exponents would drop the sign of `x`
+    assert_eq!(simplified("(x ^ 2) ^ 0.5"), parsed("(x ^ 2) ^ 0.5"));
+    assert_eq!(simplified("(x ^ 2) ^ 3"), parsed("x ^ 6"));
+    // the exponent cannot be negated without overflow
+    simplified("2 ^ -9223372036854775808");
+
+    // simplified derivatives are stored as functions
+    let mut repl = Repl::new();
+    repl.run("f(x) = x ^ 2 / 4").unwrap();
+    repl.run("diff(f, x)").unwrap();
+    expect!(repl, "f'(1)", lovm::Value::F64(0.5));
+}
+
+#[test]
+fn constant_folding() {
+    let parser = ExprParser::new();
+    let ctx = optimize::FoldContext::new();
+    let folded = |raw| optimize::fold(&parser.parse(raw).unwrap(), &ctx);
+    let parsed = |raw| parser.parse(raw).unwrap();
+
+    assert_eq!(folded("2 * 3 + x"), parsed("6 + x"));
+    assert_eq!(folded("7 / 2"), parsed("3"));
+    assert_eq!(folded("7.0 / 2"), parsed("3.5"));
+    assert_eq!(folded("(1 + 1 / 1000000) ^ 1000000"), parsed("1"));
+    assert_eq!(folded("1 / 0"), parsed("1 / 0"));
+    assert_eq!(folded("2 < 3 && true"), parsed("true"));
+
+    let mut repl = Repl::new();
+    let c = String::from("c");
+    repl.runtime
+        .define_const(&c, ast::Value::Numeric(NumType::Natural(3)))
+        .unwrap();
+    let t = String::from("t");
+    assert!(repl
+        .runtime
+        .define_const(&t, ast::Value::Tuple(vec![]))
+        .is_err());
+
+    expect!(repl, "2 * c", lovm::Value::I64(6));
+    assert!(repl.run("c = 4").is_err());
+
+    // a function of the same name does not replace the constant
+    repl.run("c(x) = x + 1").unwrap();
+    expect!(repl, "c(c)", lovm::Value::I64(4));
+    assert!(repl.run("c = 4").is_err());
+}
+
+#[test]
+fn tail_calls() {
+    let mut repl = Repl::new();
+
+    repl.run("loop(0, acc) = acc").unwrap();
+    repl.run("loop(n, acc) = loop(n - 1, acc + n)").unwrap();
+    expect!(repl, "loop(1000000, 0)", lovm::Value::I64(500000500000));
+
+    // tail calls may change the argument count
+    repl.run("count(n) = count(n, 0)").unwrap();
+    repl.run("count(0, c) = c").unwrap();
+    repl.run("count(n, c) = count(n - 1, c + 1)").unwrap();
+    expect!(repl, "count(1000000)", lovm::Value::I64(1000000));
+}
+
+#[test]
+fn jump_tables() {
+    let mut repl = Repl::new();
+
+    for i in 0..8 {
+        repl.run(&format!("f({}) = {}", i, i * 2)).unwrap();
+    }
+    repl.run("f(x) = -1").unwrap();
+    expect!(repl, "f(5)", lovm::Value::I64(10));
+    expect!(repl, "f(8)", lovm::Value::I64(-1));
+
+    // arguments that are not numbers skip the table
+    expect!(repl, "f((1, 2))", lovm::Value::I64(-1));
+    expect!(repl, "f('a')", lovm::Value::I64(-1));
+
+    // constants of different types can be mixed
+    for i in 0..4 {
+        repl.run(&format!("g({}) = {}", i, i)).unwrap();
+    }
+    repl.run("g(1.5) = 15").unwrap();
+    repl.run("g('b') = 20").unwrap();
+    repl.run("g('a') = 10").unwrap();
+    expect!(repl, "g(3)", lovm::Value::I64(3));
+    expect!(repl, "g(1.5)", lovm::Value::I64(15));
+    expect!(repl, "g('a')", lovm::Value::I64(10));
+}
+
+#[test]
+fn memoization() {
+    let mut repl = Repl::new();
+
+    repl.run("f(0) = 0").unwrap();
+    repl.run("f(1) = 1").unwrap();
+    repl.run("f(x) = f(x - 1) + f(x - 2)").unwrap();
+    repl.run("memo(f)").unwrap();
+
+    expect!(repl, "f(90)", lovm::Value::I64(2880067194370816120));
+
+    // changing a dependency drops cached results
+    repl.run("k = 2").unwrap();
+    repl.run("g(x) = x * k").unwrap();
+    repl.run("memo(g)").unwrap();
+    expect!(repl, "g(3)", lovm::Value::I64(6));
+    repl.run("k = 3").unwrap();
+    expect!(repl, "g(3)", lovm::Value::I64(9));
+
+    repl.run("h(x) = println(x)").unwrap();
+    assert!(repl.run("memo(h)").is_err());
+    // builtins reading files are registered as impure natives
+    repl.run("load(path) = csv(path)").unwrap();
+    assert!(repl.run("memo(load)").is_err());
+    repl.runtime
+        .register_native("now", 0, |_| Ok(lovm::Value::I64(0)));
+    repl.run("stamp(x) = now()").unwrap();
+    assert!(repl.run("memo(stamp)").is_err());
+
+    // `memo` has no value to assign
+    assert!(repl.run("m = memo(g)").is_err());
+
+    // user-defined `memo` and `simplify` are not intercepted
+    repl.run("memo(x) = x + 1").unwrap();
+    expect!(repl, "memo(1)", lovm::Value::I64(2));
+    repl.run("simplify(x) = x * 2").unwrap();
+    expect!(repl, "simplify(3)", lovm::Value::I64(6));
+}
+
+#[test]
+fn incremental_build() {
+    let mut repl = Repl::new();
+
+    repl.load("fib(0) = 0\nfib(1) = 1\n\nfib(n) = fib(n - 1) + fib(n - 2)\n")
+        .unwrap();
+    expect!(repl, "fib(20)", lovm::Value::I64(6765));
+
+    // only the changed function is rebuilt, the others stay loaded
+    repl.run("sq(x) = x * x").unwrap();
+    expect!(repl, "sq(fib(10))", lovm::Value::I64(3025));
+    repl.run("fib(1) = 2").unwrap();
+    expect!(repl, "fib(2)", lovm::Value::I64(2));
+    expect!(repl, "sq(3)", lovm::Value::I64(9));
+}
+
+#[test]
+fn native_functions() {
+    let mut repl = Repl::new();
+
+    repl.runtime.register_native("price", 1, |args| match args {
+        [lovm::Value::I64(n)] => Ok(lovm::Value::I64(n * 10)),
+        _ => Err("price expects an article number".to_string()),
+    });
+    expect!(repl, "price(3)", lovm::Value::I64(30));
+
+    // localc overloads with constants are dispatched before the native
+    repl.run("price(0) = 1").unwrap();
+    repl.run("total(x, n) = n * price(x)").unwrap();
+    expect!(repl, "price(0)", lovm::Value::I64(1));
+    expect!(repl, "total(2, 3)", lovm::Value::I64(60));
+    assert!(repl.run("price(1.5)").is_err());
+
+    // results of natives are not cached
+    assert!(repl.run("memo(total)").is_err());
+
+    // a general overload replaces the native
+    repl.run("price(x) = x").unwrap();
+    expect!(repl, "price(0)", lovm::Value::I64(1));
+    expect!(repl, "price(3)", lovm::Value::I64(3));
+}
+
+#[test]
+fn value_conversion() {
+    use std::collections::HashMap;
+    use std::convert::TryFrom;
+
+    let mut repl = Repl::new();
+
+    let result = repl.eval("(1, (2.5, 3))").unwrap().unwrap();
+    assert_eq!(
+        result,
+        ast::Value::Tuple(vec![
+            Expr::from(1),
+            Expr::Value(ast::Value::Tuple(vec![Expr::from(2.5), Expr::from(3)])),
+        ])
+    );
+    assert!(Vec::<i64>::try_from(result).is_err());
+
+    let result = repl.eval("(1, 2, 3)").unwrap().unwrap();
+    assert_eq!(Vec::<i64>::try_from(result), Ok(vec![1, 2, 3]));
+    assert_eq!(repl.eval("1 == 1"), Ok(Some(ast::Value::from(true))));
+
+    let result = repl.eval("{ 5 = 10, 4 = 20 }").unwrap().unwrap();
+    let set = HashMap::<i64, i64>::try_from(result).unwrap();
+    assert_eq!(set[&5], 10);
+    assert_eq!(set[&4], 20);
+
+    repl.set("prices", vec![1.5, 2.5]).unwrap();
+    let prices = repl.runtime.global("prices").unwrap().unwrap();
+    assert_eq!(Vec::<f64>::try_from(prices), Ok(vec![1.5, 2.5]));
+}
+
+#[test]
+fn pretty_printing() {
+    let mut repl = Repl::new();
+
+    let show = |repl: &mut Repl, line: &str| {
+        let result = repl.run(line).unwrap().unwrap();
+        repl.format(&result)
+    };
+
+    assert_eq!(show(&mut repl, "2 + 2"), "4");
+    assert_eq!(show(&mut repl, "1.0 * 2"), "2.0");
+    assert_eq!(show(&mut repl, "1 == 2"), "false");
+    assert_eq!(show(&mut repl, "(1, (2.5, 3))"), "(1, (2.5, 3))");
+    assert_eq!(show(&mut repl, "{ 5 = 10, 4 = 20 }"), "{4 = 20, 5 = 10}");
+    assert_eq!(show(&mut repl, "{ 10 = 1, 9 = 2 }"), "{9 = 2, 10 = 1}");
+    assert_eq!(
+        show(&mut repl, "{ 'b' = 1, 2.5 = 2, 1 = 3 }"),
+        "{1 = 3, 2.5 = 2, 'b' = 1}"
+    );
+
+    // floats are printed without exponent to be parsed back
+    assert_eq!(show(&mut repl, "10.0 ^ 21"), "1000000000000000000000.0");
+    assert_eq!(show(&mut repl, "1.0 / 10000000"), "0.0000001");
+    assert_eq!(show(&mut repl, "('it''s', '''')"), "('it''s', '''')");
+
+    repl.pretty = Pretty::new().with_precision(3).with_max_items(2);
+    assert_eq!(show(&mut repl, "1.0 / 3"), "0.333");
+    assert_eq!(show(&mut repl, "1.0 / 2"), "0.5");
+    assert_eq!(show(&mut repl, "(1, 2, 3, 4)"), "(1, 2, ... 2 more)");
+}
+
+#[test]
+fn json_format() {
+    let mut repl = Repl::new();
+    repl.pretty = Pretty::new().with_format("json".parse().unwrap());
+
+    let show = |repl: &mut Repl, line: &str| {
+        let result = repl.run(line).unwrap().unwrap();
+        repl.format(&result)
+    };
+
+    assert_eq!(show(&mut repl, "1.0 * 2"), "2.0");
+    assert_eq!(show(&mut repl, "(1, (2.5, 1 == 1))"), "[1, [2.5, true]]");
+    assert_eq!(
+        show(&mut repl, "{ 'b' = 10, 'a' = (1, 2) }"),
+        "{\"a\": [1, 2], \"b\": 10}"
+    );
+    // keys that are not strings would collide with string keys in an object
+    assert_eq!(
+        show(&mut repl, "{ 10 = 1, 9 = (1, 2), '9' = 3 }"),
+        "[[9, [1, 2]], [10, 1], [\"9\", 3]]"
+    );
+    assert!("xml".parse::<Format>().is_err());
+}
+
+#[test]
+fn meta_commands() {
+    let mut repl = Repl::new();
+
+    repl.run("f(0) = 1").unwrap();
+    repl.run("f(n) = n * f(n - 1)").unwrap();
+    repl.run("k = 3").unwrap();
+
+    assert!(repl.command(":help").unwrap().contains(":bytecode"));
+    let list = repl.command(":list").unwrap();
+    assert!(list.contains("f(0) f(n)"));
+    assert!(list.contains("k = 3"));
+    assert_eq!(repl.command(":show f").unwrap().lines().count(), 2);
+    assert!(repl.command(":show vadd").unwrap().contains("<native>"));
+    assert!(repl
+        .command(":time f(5)")
+        .unwrap()
+        .starts_with("120\ntime: "));
+    let call = repl.command(":bytecode f(k)").unwrap();
+    let dispatch = repl.command(":bytecode f").unwrap();
+    assert!(call.lines().count() < dispatch.lines().count());
+    assert!(repl.command(":show").is_err());
+    assert!(repl.command(":unknown").is_err());
+
+    // tests run in parallel with other test processes
+    let path = std::env::temp_dir().join(format!("localc-session-{}.lc", std::process::id()));
+    let path = path.to_str().unwrap();
+    repl.command(&format!(":save {}", path)).unwrap();
+
+    repl.command(":undef k").unwrap();
+    assert!(!repl.command(":list").unwrap().contains("k = 3"));
+    repl.command(":undef f").unwrap();
+    assert!(repl.command(":show f").is_err());
+
+    repl.command(":reset").unwrap();
+    assert_eq!(repl.command(&format!(":load {}", path)).unwrap(), "");
+    expect!(repl, "f(k)", lovm::Value::I64(6));
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn line_input() {
+    use crate::repl::editor::is_incomplete;
+
+    assert!(is_incomplete("f(x) = (x,"));
+    assert!(is_incomplete("{ 1 = (2, 3)"));
+    assert!(!is_incomplete("f'(x) = (x, 1)"));
+    assert!(!is_incomplete("println('(')"));
+
+    let mut repl = Repl::new();
+    repl.run("fib(0) = 0").unwrap();
+    repl.run("fibs = 1").unwrap();
+    let names = repl.completions();
+    assert!(names.contains(&"fib".to_string()));
+    assert!(names.contains(&"fibs".to_string()));
+}
+
+#[test]
+fn scripts() {
+    use crate::repl::script::ErrorKind;
+
+    let mut repl = Repl::new();
+    let errors = repl.run_script("cond.lc", include_str!("../../example/cond.lc"), false);
+    assert!(errors.is_empty());
+
+    let errors = repl.run_script(
+        "assert-fail.lc",
+        include_str!("../../example/assert-fail.lc"),
+        false,
+    );
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].kind, ErrorKind::Runtime);
+    assert_eq!((errors[0].line, errors[0].col), (1, 1));
+    assert_eq!(errors[0].statement, "assert(1 != 1)");
+
+    let src = "x = 1\n\ny = (x,\n  2 +)\nassert(false)\n  assert(x == 2, 'x is not 2')";
+    let errors = repl.run_script("test.lc", src, false);
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].kind, ErrorKind::Parse);
+    assert_eq!(errors[0].line, 4);
+    assert!(errors[0].to_string().starts_with("test.lc:4:"));
+
+    let errors = repl.run_script("test.lc", src, true);
+    assert_eq!(errors.len(), 3);
+    assert_eq!((errors[1].line, errors[1].col), (5, 1));
+    assert_eq!((errors[2].line, errors[2].col), (6, 3));
+    assert!(errors[2].message.contains("x is not 2"));
+}
+
+#[test]
+fn compile_units() {
+    use std::io::Write;
+
+    let dir = std::env::temp_dir();
+    let path = |name: &str| {
+        dir.join(format!("localc-{}-{}.lc", name, std::process::id()))
+            .to_str()
+            .unwrap()
+            .to_string()
+    };
+
+    let source = path("unit");
+    std::fs::File::create(&source)
+        .unwrap()
+        .write_all(b"fact(0) = 1 # base case\nfact(n) = n * fact(n - 1)\n\nsq(x) = (x # (\n * x)\n")
+        .unwrap();
+    let unit = compiler::compile_files(&vec![source.clone()]).unwrap();
+
+    let mut repl = Repl::new();
+    repl.runtime.load_unit(unit).unwrap();
+    expect!(repl, "fact(5)", lovm::Value::I64(120));
+    expect!(repl, "sq(fact(3))", lovm::Value::I64(36));
+
+    let invalid = path("invalid");
+    std::fs::File::create(&invalid)
+        .unwrap()
+        .write_all(b"f(x) = x\ng(x) = (x +\n")
+        .unwrap();
+    let err = compiler::compile_files(&vec![invalid.clone()]).unwrap_err();
+    assert!(err.starts_with(&format!("{}:2:", invalid)));
+    assert!(compiler::compile_files(&vec![path("missing")]).is_err());
+
+    std::fs::remove_file(source).unwrap();
+    std::fs::remove_file(invalid).unwrap();
+
+    // statements are split the same way for every output of `localc-cc`
+    assert_eq!(
+        localc_cc_lib::formatter::statements("# f\nf(x) = (x +\n 1) # g\n\ny = 2"),
+        vec![
+            (2, "f(x) = (x +  1) ".to_string()),
+            (5, "y = 2".to_string())
+        ]
+    );
+}
+
+#[test]
+fn print_expressions() {
+    let parser = ExprParser::new();
+    let print = |raw| parser.parse(raw).unwrap().to_string();
+
+    assert_eq!(print("1+2*3"), "1 + 2 * 3");
+    assert_eq!(print("(1+2)*3"), "(1 + 2) * 3");
+    assert_eq!(print("1-(2-3)"), "1 - (2 - 3)");
+    assert_eq!(print("(1-2)-3"), "1 - 2 - 3");
+    assert_eq!(
+        print("f(x,y)=x^2.0 == y || (x && y)"),
+        "f(x, y) = x ^ 2.0 == y || x && y"
+    );
+    assert_eq!(print("{ 5 = 10, 1.0 }"), "{5 = 10, 1.0}");
+    assert_eq!(print("((1,),(),'a', Nil)"), "((1,), (), 'a', Nil)");
+    assert_eq!(print("'it''s'"), "'it''s'");
+    // non-finite numbers are no literals
+    assert_eq!(print("inf(NaN) = NaN"), "inf(NaN) = NaN");
+    let values = vec![std::f64::INFINITY, std::f64::NEG_INFINITY, std::f64::NAN]
+        .into_iter()
+        .map(|n| Expr::Value(ast::Value::Numeric(NumType::Rational(n))))
+        .collect::<Vec<_>>();
+    let printed = Expr::Func("f".to_string(), values).to_string();
+    assert_eq!(printed, "f((1.0 / 0), (-1.0 / 0), (0.0 / 0))");
+    assert_eq!(print(&printed), "f(1.0 / 0, -1.0 / 0, 0.0 / 0)");
+}
+
+// random expressions in the shape the parser produces them
+struct ExprGen(u64);
+
+impl ExprGen {
+    fn next(&mut self, n: u64) -> u64 {
+        // xorshift
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0 % n
+    }
+
+    fn expr(&mut self, depth: usize) -> Expr {
+        use crate::ast::Value::*;
+        const OPS: &[Operator] = &[
+            Operator::Add,
+            Operator::Sub,
+            Operator::Mul,
+            Operator::Div,
+            Operator::Pow,
+            Operator::Rem,
+            Operator::Eq,
+            Operator::Ne,
+            Operator::Ge,
+            Operator::Gt,
+            Operator::Le,
+            Operator::Lt,
+            Operator::And,
+            Operator::Or,
+            Operator::Store,
+        ];
+        let kind = if depth == 0 {
+            self.next(5)
+        } else {
+            self.next(9)
+        };
+        match kind {
+            0 => Expr::from(self.next(2000) as i64 - 1000),
+            1 => match self.next(8) {
+                0 => Expr::from(std::f64::INFINITY),
+                _ => Expr::from((self.next(2000) as f64 - 1000.) / 8.),
+            },
+            2 => Expr::from(self.next(2) == 0),
+            3 => Expr::Ref(["x", "y'", "arg0", "f"][self.next(4) as usize].to_string()),
+            4 => Expr::from(["", "text", "a b", "it's"][self.next(4) as usize]),
+            5 | 6 => {
+                let op = OPS[self.next(OPS.len() as u64) as usize].clone();
+                let lhs = self.expr(depth - 1);
+                let rhs = self.expr(depth - 1);
+                Expr::Comp(op, Box::new(lhs), Box::new(rhs))
+            }
+            7 => {
+                let args = (0..self.next(4)).map(|_| self.expr(depth - 1)).collect();
+                if self.next(2) == 0 {
+                    Expr::Func("g".to_string(), args)
+                } else {
+                    Expr::Value(Tuple(args))
+                }
+            }
+            _ => {
+                let items = (0..self.next(3))
+                    .map(|_| match self.expr(depth - 1) {
+                        // the parser turns assignments into keyed entries
+                        Expr::Comp(Operator::Store, key, value) => (Some(*key), *value),
+                        value => (None, value),
+                    })
+                    .collect();
+                Expr::Value(Set(items))
+            }
+        }
+    }
+}
+
+#[test]
+fn print_round_trip() {
+    let parser = ExprParser::new();
+    let mut gen = ExprGen(0x2545_f491_4f6c_dd1d);
+
+    for _ in 0..2000 {
+        let expr = gen.expr(4);
+        let printed = expr.to_string();
+        assert_eq!(parser.parse(&printed).ok(), Some(expr), "`{}`", printed);
+    }
+}
+
+#[test]
+fn formatting() {
+    use localc_cc_lib::formatter::format_source;
+
+    let src = "
+# fibonacci
+fib(0)=0
+fib(1) = 1 # base
+fib(n)=fib(n-1)+fib(n-2)
+
+
+g(0)=1
+g(x,y)=x*(y)
+x=(1,
+  2) # pair
+s = '#' # hash
+";
+    let expected = "# fibonacci
+fib(0) = 0
+fib(1) = 1 # base
+fib(n) = fib(n - 1) + fib(n - 2)
+
+g(0)    = 1
+g(x, y) = x * y
+x = (1, 2) # pair
+s = '#' # hash
+";
+    assert_eq!(format_source(src).unwrap(), expected);
+    assert_eq!(format_source(expected).unwrap(), expected);
+    assert!(format_source("f(x) = (x").is_err());
+
+    // comments are ignored when running code
+    let mut repl = Repl::new();
+    assert_eq!(repl.run("# nothing"), Ok(None));
+    expect!(repl, "1 + 1 # two", lovm::Value::I64(2));
+    assert!(repl.run_script("test.lc", src, false).is_empty());
+}
+
+#[test]
+fn language_server() {
+    use crate::lsp::{read_message, write_message, Server};
+    use serde_json::{json, Value};
+
+    let mut server = Server::new();
+    let uri = "file:///test.lc";
+    let src = "f(0) = 1\nf(n) = n * f(n - 1)\nx = sqrt(f(3)) + y\ng(n) = (n\n  + 1) *\nh(n) = undefined(n)\n";
+
+    let init =
+        server.handle(&json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}}));
+    assert_eq!(init[0]["id"], 1);
+    assert_eq!(init[0]["result"]["capabilities"]["hoverProvider"], true);
+
+    let open = json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/didOpen",
+        "params": {"textDocument": {"uri": uri, "languageId": "localc", "version": 1, "text": src}},
+    });
+    let published = server.handle(&open);
+    assert_eq!(published[0]["method"], "textDocument/publishDiagnostics");
+    let diagnostics = published[0]["params"]["diagnostics"].as_array().unwrap();
+    let messages = diagnostics
+        .iter()
+        .map(|diag| {
+            let start = &diag["range"]["start"];
+            (
+                start["line"].clone(),
+                start["character"].clone(),
+                diag["message"].as_str().unwrap(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(messages.len(), 3);
+    assert_eq!((&messages[0].0, &messages[0].1), (&json!(4), &json!(9)));
+    assert!(messages[0].2.starts_with("parse error"));
+    assert_eq!(messages[1], (json!(2), json!(17), "unknown name `y`"));
+    assert_eq!(
+        messages[2],
+        (json!(5), json!(7), "unknown function `undefined`")
+    );
+
+    let at = |id: i64, method: &str, line: i64, character: i64| {
+        json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": {"textDocument": {"uri": uri}, "position": {"line": line, "character": character}},
+        })
+    };
+
+    // overloads from the document and the runtime
+    let hover = server.handle(&at(2, "textDocument/hover", 1, 12));
+    assert_eq!(
+        hover[0]["result"]["contents"]["value"],
+        "```\nf(0) = 1\nf(n) = n * f(n - 1)\n```"
+    );
+    let hover = server.handle(&at(3, "textDocument/hover", 2, 5));
+    let text = hover[0]["result"]["contents"]["value"].as_str().unwrap();
+    assert!(text.contains("sqrt(arg0) = <native>"));
+    assert!(text.contains("sqrt(arg0, arg1) = <native>"));
+
+    let definition = server.handle(&at(4, "textDocument/definition", 2, 9));
+    assert_eq!(
+        definition[0]["result"]["range"]["start"],
+        json!({"line": 0, "character": 0})
+    );
+    let definition = server.handle(&at(5, "textDocument/definition", 2, 0));
+    assert_eq!(
+        definition[0]["result"]["range"]["end"],
+        json!({"line": 2, "character": 18})
+    );
+
+    let completion = server.handle(&at(6, "textDocument/completion", 0, 0));
+    let labels = completion[0]["result"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| item["label"].as_str().unwrap().to_string())
+        .collect::<Vec<_>>();
+    assert!(labels.contains(&"f".to_string()));
+    assert!(labels.contains(&"fac".to_string()));
+    assert!(labels.contains(&"pi".to_string()));
+
+    let symbols = server.handle(&at(7, "textDocument/documentSymbol", 0, 0));
+    let names = symbols[0]["result"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|symbol| symbol["detail"].as_str().unwrap().to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec!["f(0)", "f(n)", "x", "h(n)"]);
+
+    let unknown = server.handle(&at(8, "textDocument/rename", 0, 0));
+    assert_eq!(unknown[0]["error"]["code"], -32601);
+
+    // characters are counted in utf-16 code units
+    let text = "s = 'ä😀' + z\nt = 'ä😀' + s\n";
+    let change = json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/didChange",
+        "params": {"textDocument": {"uri": uri, "version": 2}, "contentChanges": [{"text": text}]},
+    });
+    let published = server.handle(&change);
+    let diagnostic = &published[0]["params"]["diagnostics"][0];
+    assert_eq!(
+        diagnostic["range"]["start"],
+        json!({"line": 0, "character": 12})
+    );
+    assert_eq!(
+        diagnostic["range"]["end"],
+        json!({"line": 0, "character": 13})
+    );
+    let definition = server.handle(&at(9, "textDocument/definition", 1, 12));
+    assert_eq!(
+        definition[0]["result"]["range"]["end"],
+        json!({"line": 0, "character": 13})
+    );
+
+    // the same session through the wire format
+    let mut input = vec![];
+    write_message(
+        &mut input,
+        &json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}}),
+    )
+    .unwrap();
+    // malformed bodies do not end the session
+    input.extend_from_slice(b"Content-Length: 5\r\n\r\n{oops");
+    for msg in [
+        open,
+        json!({"jsonrpc": "2.0", "id": 2, "method": "shutdown"}),
+        json!({"jsonrpc": "2.0", "method": "exit"}),
+    ]
+    .iter()
+    {
+        write_message(&mut input, msg).unwrap();
+    }
+    let mut server = Server::with_repl(Repl::new());
+    let mut output = vec![];
+    server.serve(&mut input.as_slice(), &mut output).unwrap();
+    assert_eq!(server.exit_code(), 0);
+
+    let mut output = output.as_slice();
+    let mut replies: Vec<Value> = vec![];
+    while let Some(msg) = read_message(&mut output).unwrap() {
+        replies.push(msg);
+    }
+    assert_eq!(replies.len(), 4);
+    assert_eq!(replies[1]["error"]["code"], -32700);
+    assert_eq!(replies[1]["id"], Value::Null);
+    assert_eq!(replies[2]["method"], "textDocument/publishDiagnostics");
+    assert_eq!(
+        replies[3],
+        json!({"jsonrpc": "2.0", "id": 2, "result": null})
+    );
+}
+
+#[test]
+fn kernel() {
+    use crate::kernel::Kernel;
+    use serde_json::{json, Value};
+    use std::sync::atomic::Ordering;
+
+    let mut kernel = Kernel::new(Repl::with_stdlib());
+
+    let execute = |kernel: &mut Kernel, code: &str| {
+        kernel.handle(&json!({"id": "x", "type": "execute", "code": code}))
+    };
+
+    let reply = execute(&mut kernel, "f(x) = x ^ 2\nprintln('f', f(3))\nf(2) + 1");
+    assert_eq!(reply["id"], "x");
+    assert_eq!(reply["status"], "ok");
+    assert_eq!(reply["result"], json!({"type": "int", "value": 5}));
+    assert_eq!(reply["data"]["text/plain"], "5");
+    assert_eq!(reply["stdout"], "f 9\n");
+
+    let reply = execute(&mut kernel, "((1, 2), (3, 4.5))");
+    assert_eq!(reply["result"]["type"], "tuple");
+    assert_eq!(
+        reply["result"]["items"][1]["items"][1],
+        json!({"type": "float", "value": 4.5})
+    );
+    assert_eq!(
+        reply["data"]["text/html"],
+        "<table><tr><td>1</td><td>2</td></tr><tr><td>3</td><td>4.5</td></tr></table>"
+    );
+
+    // printing alone has no result
+    let reply = execute(&mut kernel, "print('a')");
+    assert_eq!(reply["result"], Value::Null);
+    assert_eq!(reply["stdout"], "a");
+    let reply = execute(&mut kernel, "println(1, 2, 3, 4, 5, 6)\nprintln()");
+    assert_eq!(reply["stdout"], "1 2 3 4 5 6\n\n");
+
+    let reply = execute(&mut kernel, "x = 1\ny = (x\n  + undefined(1))");
+    assert_eq!(reply["status"], "error");
+    assert_eq!(reply["error"]["kind"], "runtime");
+    assert_eq!(
+        reply["error"]["span"]["start"],
+        json!({"line": 2, "col": 1})
+    );
+    assert_eq!(reply["error"]["span"]["end"], json!({"line": 3, "col": 18}));
+
+    let reply = execute(&mut kernel, "1 +* 2");
+    assert_eq!(reply["error"]["kind"], "parse");
+    assert_eq!(
+        reply["error"]["span"]["start"],
+        json!({"line": 1, "col": 4})
+    );
+
+    let reply = execute(&mut kernel, ":show f");
+    assert_eq!(reply["data"]["text/plain"], "f(x) = x ^ 2");
+
+    let reply = kernel.handle(&json!({"id": 1, "type": "complete", "code": "1 + fa", "cursor": 6}));
+    assert_eq!(reply["matches"], json!(["fac"]));
+    assert_eq!(reply["cursor_start"], 4);
+
+    let reply = kernel.handle(&json!({"id": 2, "type": "inspect", "code": "sqrt(x)", "cursor": 2}));
+    assert_eq!(reply["found"], true);
+    assert_eq!(
+        reply["data"]["text/plain"],
+        "sqrt(arg0) = <native>\nsqrt(arg0, arg1) = <native>"
+    );
+    let reply = kernel.handle(&json!({"id": 3, "type": "inspect", "code": "x + 1", "cursor": 0}));
+    assert_eq!(reply["data"]["text/plain"], "x = 1");
+
+    // an interrupt stops the execution at the next statement or output
+    kernel.interrupter().store(true, Ordering::SeqCst);
+    let reply = execute(&mut kernel, "println(1)");
+    assert_eq!(reply["error"]["kind"], "interrupted");
+    let reply = kernel.handle(&json!({"id": 4, "type": "interrupt"}));
+    assert_eq!(reply, json!({"id": 4, "status": "ok"}));
+    assert_eq!(execute(&mut kernel, "f(4)")["result"]["value"], 16);
+
+    // code that never prints is stopped as well
+    let interrupter = kernel.interrupter();
+    let timer = std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        interrupter.store(true, Ordering::SeqCst);
+    });
+    let reply = execute(&mut kernel, "spin(n) = spin(n + 1)\nspin(0)");
+    timer.join().unwrap();
+    assert_eq!(reply["error"]["kind"], "interrupted");
+    kernel.handle(&json!({"id": 5, "type": "interrupt"}));
+    assert_eq!(execute(&mut kernel, "f(5)")["result"]["value"], 25);
+
+    let reply = kernel.handle(&json!({"id": 6, "type": "shutdown"}));
+    assert_eq!(reply["error"]["kind"], "protocol");
+
+    // the same session through the wire format
+    let input = "{\"id\": 1, \"type\": \"execute\", \"code\": \"2 * 3\"}\nnot json\n";
+    let mut output = vec![];
+    let mut kernel = Kernel::new(Repl::new());
+    kernel
+        .serve(std::io::Cursor::new(input.as_bytes().to_vec()), &mut output)
+        .unwrap();
+    let replies = String::from_utf8(output).unwrap();
+    let replies = replies
+        .lines()
+        .map(|line| serde_json::from_str::<Value>(line).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(replies.len(), 2);
+    assert_eq!(replies[0]["result"]["value"], 6);
+    assert_eq!(replies[1]["id"], Value::Null);
+    assert_eq!(replies[1]["error"]["kind"], "protocol");
+}
+
+#[test]
+fn json_data() {
+    use std::io::Write;
+
+    let path = std::env::temp_dir().join("localc_json_data.json");
+    std::fs::File::create(&path)
+        .unwrap()
+        .write_all(br#"{"values": [1, 2.5], "name": "a", "ok": true}"#)
+        .unwrap();
+
+    let mut repl = Repl::new();
+    repl.pretty = Pretty::new().with_format(Format::Json);
+    repl.run(&format!("data = json('{}')", path.display()))
+        .unwrap();
+
+    let data = repl.runtime.vm.data.globals["data"].clone();
+    assert_eq!(
+        repl.format(&data),
+        "{\"name\": \"a\", \"ok\": true, \"values\": [1, 2.5]}"
+    );
+    assert!(repl.run("json('does/not/exist.json')").is_err());
+
+    // lovm has no value for `null`
+    std::fs::File::create(&path)
+        .unwrap()
+        .write_all(br#"{"values": [1, null]}"#)
+        .unwrap();
+    assert!(repl.run(&format!("json('{}')", path.display())).is_err());
+
+    // results and errors of scripts
+    let mut results = vec![];
+    let errors = repl.run_script_with(
+        "test.lc",
+        "1 + 1\nx = 2\n(x, 'b')\n",
+        false,
+        |repl, result| results.push(repl.format(result)),
+    );
+    assert!(errors.is_empty());
+    assert_eq!(results, vec!["2", "[2, \"b\"]"]);
+
+    let errors = repl.run_script("test.lc", "x = 1\ny = (x +\n  undefined(1))", false);
+    assert_eq!(
+        errors[0].to_json()["span"],
+        serde_json::json!({"start": {"line": 2, "col": 1}, "end": {"line": 3, "col": 16}})
+    );
+    let errors = repl.run_script("test.lc", "1 +* 2", false);
+    assert_eq!(errors[0].to_json()["kind"], "parse");
+    assert_eq!(
+        errors[0].to_json()["span"],
+        serde_json::json!({"start": {"line": 1, "col": 4}, "end": {"line": 1, "col": 5}})
+    );
+}
+
+#[test]
+fn csv_tables() {
+    use crate::runtime::builtin::table::parse_csv;
+    use std::io::{Read, Write};
+
+    assert_eq!(
+        parse_csv("a,\"b,\"\"c\"\"\"\r\n1,\"x\ny\"").unwrap(),
+        vec![vec!["a", "b,\"c\""], vec!["1", "x\ny"]]
+    );
+    assert!(parse_csv("\"open").is_err());
+    assert_eq!(
+        parse_csv("a,b\n\r\n1,\"\"\n\n").unwrap(),
+        vec![vec!["a", "b"], vec!["1", ""]]
+    );
+
+    let dir = std::env::temp_dir();
+    let input = dir.join(format!("localc_csv_tables_{}.csv", std::process::id()));
+    let output = dir.join(format!("localc_csv_tables_out_{}.csv", std::process::id()));
+    std::fs::File::create(&input)
+        .unwrap()
+        .write_all(b"name,price,amount\napple,1.5,3\npear,,2\nplum,0.5,1\n\n")
+        .unwrap();
+
+    let mut repl = Repl::new();
+    repl.run(&format!("t = csv('{}')", input.display()))
+        .unwrap();
+    repl.run(&format!("s = csv('{}', true)", input.display()))
+        .unwrap();
+
+    assert_eq!(
+        repl.eval("column(t, 0)").unwrap(),
+        Some(ast::Value::from(vec!["name", "apple", "pear", "plum"]))
+    );
+    expect!(repl, "sum(column(t, 'amount'))", lovm::Value::I64(6));
+    expect!(repl, "sum(column(s, 'price'))", lovm::Value::F64(2.0));
+    expect!(repl, "mean(column(s, 'price'))", lovm::Value::F64(1.0));
+    expect!(repl, "count(column(t, 'price'))", lovm::Value::I64(2));
+    assert!(repl.run("column(t, 'weight')").is_err());
+
+    repl.run(&format!(
+        "write_csv('{}', ((1, 'a, b'), (2.5, 'c')))",
+        output.display()
+    ))
+    .unwrap();
+    let mut written = String::new();
+    std::fs::File::open(&output)
+        .unwrap()
+        .read_to_string(&mut written)
+        .unwrap();
+    assert_eq!(written, "1,\"a, b\"\n2.5,c\n");
+
+    // nested tuples are not truncated
+    let items = (0..40)
+        .map(|i| i.to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    repl.run(&format!(
+        "write_csv('{}', (({},),))",
+        output.display(),
+        items
+    ))
+    .unwrap();
+    let mut written = String::new();
+    std::fs::File::open(&output)
+        .unwrap()
+        .read_to_string(&mut written)
+        .unwrap();
+    assert_eq!(written, format!("\"({})\"\n", items));
+
+    std::fs::File::create(&input)
+        .unwrap()
+        .write_all(b"x,y,x\n1,2,3\n")
+        .unwrap();
+    assert!(repl
+        .run(&format!("csv('{}', true)", input.display()))
+        .is_err());
+}
+
+#[test]
+fn math_functions() {
+    let mut repl = Repl::with_stdlib();
+
+    expect!(repl, "pi", lovm::Value::F64(std::f64::consts::PI));
+    expect!(repl, "e", lovm::Value::F64(std::f64::consts::E));
+    assert!(repl.run("pi = 3").is_err());
+    assert!(repl.run("e = 3").is_err());
+
+    expect!(repl, "sqrt(16)", lovm::Value::I64(4));
+    expect!(repl, "sqrt(2)", lovm::Value::F64(std::f64::consts::SQRT_2));
+    expect!(repl, "sqrt(6.25)", lovm::Value::F64(2.5));
+    expect!(repl, "sqrt(3125, 5)", lovm::Value::I64(5));
+    expect!(repl, "sqrt(-27, 3)", lovm::Value::I64(-3));
+    assert!(repl.run("sqrt(-4)").is_err());
+
+    expect!(repl, "log(8, 2)", lovm::Value::F64(3.));
+    expect!(repl, "log(100, 10)", lovm::Value::F64(2.));
+    expect!(repl, "log(100)", lovm::Value::F64(2.));
+    expect!(repl, "log2(1024)", lovm::Value::F64(10.));
+    expect!(repl, "ln(e)", lovm::Value::F64(1.));
+    expect!(repl, "ln(1)", lovm::Value::F64(0.));
+    expect!(repl, "exp(0)", lovm::Value::F64(1.));
+    assert!(repl.run("ln(0)").is_err());
+
+    expect!(repl, "sin(0)", lovm::Value::F64(0.));
+    expect!(repl, "cos(0)", lovm::Value::F64(1.));
+    expect!(
+        repl,
+        "atan(1, 1) * 4",
+        lovm::Value::F64(std::f64::consts::PI)
+    );
+    expect!(repl, "acos(1)", lovm::Value::F64(0.));
+    expect!(repl, "tanh(0)", lovm::Value::F64(0.));
+    expect!(repl, "acosh(1)", lovm::Value::F64(0.));
+    assert!(repl.run("asin(2)").is_err());
+
+    expect!(repl, "floor(2.7)", lovm::Value::I64(2));
+    expect!(repl, "ceil(-2.7)", lovm::Value::I64(-2));
+    expect!(repl, "round(2.5)", lovm::Value::I64(3));
+    expect!(repl, "round(3.14159, 2)", lovm::Value::F64(3.14));
+    expect!(repl, "round(1250, -2)", lovm::Value::I64(1300));
+    expect!(repl, "round(1234, -2)", lovm::Value::I64(1200));
+    expect!(repl, "round(-1250, -2)", lovm::Value::I64(-1300));
+    expect!(repl, "round(1250, -30)", lovm::Value::I64(0));
+    expect!(repl, "round(1250.0, -2)", lovm::Value::F64(1300.));
+    expect!(repl, "abs(-3)", lovm::Value::I64(3));
+    expect!(repl, "abs(-0.5)", lovm::Value::F64(0.5));
+
+    expect!(repl, "min(3, 1.5, 2)", lovm::Value::F64(1.5));
+    expect!(repl, "max(3, 1.5, 2)", lovm::Value::I64(3));
+    expect!(repl, "max((4, 9, 2))", lovm::Value::I64(9));
+    expect!(
+        repl,
+        "min(9, 8, 7, 6, 5, 4, 3, 2, 1, 0)",
+        lovm::Value::I64(0)
+    );
+    repl.run("x = 3").unwrap();
+    expect!(
+        repl,
+        "max(1, 2, 3, 4, 5, 6, 7, 8, 9, 10, x * 4)",
+        lovm::Value::I64(12)
+    );
+    expect!(
+        repl,
+        "sqrt(9223372036854775807, 1)",
+        lovm::Value::I64(9223372036854775807)
+    );
+    expect!(repl, "gcd(12, -18)", lovm::Value::I64(6));
+    expect!(repl, "lcm(4, 6)", lovm::Value::I64(12));
+    assert!(repl.run("gcd(1.5, 3)").is_err());
+
+    // calls with constant arguments are folded, definitions keep their source
+    repl.run("f(x) = sqrt(16) + x").unwrap();
+    assert_eq!(repl.show("f").unwrap(), "f(x) = sqrt(16) + x");
+    expect!(repl, "f(1)", lovm::Value::I64(5));
+}
+
+#[test]
+fn statistics() {
+    let mut repl = Repl::with_stdlib();
+
+    expect!(repl, "sum((1, 2, 3))", lovm::Value::I64(6));
+    expect!(repl, "sum((1, 2.5))", lovm::Value::F64(3.5));
+    expect!(repl, "product((2, 2.5))", lovm::Value::F64(5.));
+    expect!(repl, "mean((1, 2, 3, 4))", lovm::Value::F64(2.5));
+    expect!(repl, "median((3, 1, 2))", lovm::Value::I64(2));
+    expect!(repl, "median((4, 1, 2, 3))", lovm::Value::F64(2.5));
+    expect!(repl, "mode((3, 1, 3, 1, 2))", lovm::Value::I64(1));
+    expect!(
+        repl,
+        "variance((2, 4, 4, 4, 5, 5, 7, 9))",
+        lovm::Value::F64(32. / 7.)
+    );
+    expect!(
+        repl,
+        "stddev((1, 1, 3, 3))",
+        lovm::Value::F64((4f64 / 3.).sqrt())
+    );
+    expect!(repl, "quantile((4, 1, 3, 2), 0.5)", lovm::Value::F64(2.5));
+    expect!(repl, "quantile((1, 2, 3), 1)", lovm::Value::F64(3.));
+    expect!(
+        repl,
+        "covariance((1, 2, 3), (1, 2, 3))",
+        lovm::Value::F64(1.)
+    );
+    expect!(
+        repl,
+        "correlation((1, 2, 3), (3, 2, 1))",
+        lovm::Value::F64(-1.)
+    );
+    // strings are skipped by functions of one sample
+    expect!(repl, "mean((1, 'x', 2))", lovm::Value::F64(1.5));
+
+    let fit = repl
+        .run("regression((1, 2, 3), (3, 5, 7))")
+        .unwrap()
+        .unwrap();
+    assert_eq!(repl.format(&fit), "(2.0, 1.0, 1.0)");
+
+    assert!(repl.run("mean(('a', 'b'))").is_err());
+    assert!(repl.run("variance((1,))").is_err());
+    assert!(repl.run("sum((9223372036854775807, 1))").is_err());
+    assert!(repl.run("product((4294967296, 4294967296))").is_err());
+    assert!(repl.run("quantile((1, 2), 2)").is_err());
+    assert!(repl.run("covariance((1, 2), (1, 2, 3))").is_err());
+    assert!(repl.run("correlation((1, 1), (1, 2))").is_err());
+}
+
+#[test]
+fn collections() {
+    let mut repl = Repl::with_stdlib();
+
+    let show = |repl: &mut Repl, line: &str| {
+        let result = repl.run(line).unwrap().unwrap();
+        repl.format(&result)
+    };
+
+    expect!(repl, "len((1, 2, 3))", lovm::Value::I64(3));
+    expect!(repl, "len({ 1 = 2 })", lovm::Value::I64(1));
+    expect!(repl, "len('abc')", lovm::Value::I64(3));
+    assert_eq!(show(&mut repl, "range(0, 5)"), "(0, 1, 2, 3, 4)");
+    assert_eq!(show(&mut repl, "range(5, 0, -2)"), "(5, 3, 1)");
+    assert_eq!(
+        show(&mut repl, "range(0, 1, 0.25)"),
+        "(0.0, 0.25, 0.5, 0.75)"
+    );
+    assert!(repl.run("range(0, 1, 0)").is_err());
+
+    assert_eq!(
+        show(&mut repl, "zip((1, 2, 3), (4, 5))"),
+        "((1, 4), (2, 5))"
+    );
+    assert_eq!(show(&mut repl, "sort((3, 1.5, 2))"), "(1.5, 2, 3)");
+    assert_eq!(show(&mut repl, "reverse((1, 2, 3))"), "(3, 2, 1)");
+    assert_eq!(show(&mut repl, "unique((1, 2, 1, 3, 2))"), "(1, 2, 3)");
+    expect!(repl, "contains((1, 2, 3), 2.0)", lovm::Value::T(true));
+    expect!(repl, "contains({ 5 = 10 }, 10)", lovm::Value::T(false));
+    expect!(repl, "contains('localc', 'cal')", lovm::Value::T(true));
+    assert_eq!(show(&mut repl, "keys({ 5 = 10, 4 = 20 })"), "(4, 5)");
+    assert_eq!(show(&mut repl, "values({ 5 = 10, 4 = 20 })"), "(20, 10)");
+    assert_eq!(show(&mut repl, "concat((1, 2), (3, 4))"), "(1, 2, 3, 4)");
+    assert_eq!(show(&mut repl, "take((1, 2, 3), 2)"), "(1, 2)");
+    assert_eq!(show(&mut repl, "drop((1, 2, 3), 1)"), "(2, 3)");
+    assert_eq!(
+        show(&mut repl, "zip({ 2 = 'b', 1 = 'a' }, (3, 4))"),
+        "(('a', 3), ('b', 4))"
+    );
+    assert_eq!(
+        show(&mut repl, "reverse({ 1 = 'a', 2 = 'b' })"),
+        "('b', 'a')"
+    );
+    assert_eq!(show(&mut repl, "take({ 1 = 'a', 2 = 'b' }, 1)"), "('a')");
+    assert_eq!(show(&mut repl, "drop({ 1 = 'a', 2 = 'b' }, 1)"), "('b')");
+
+    // callbacks are user-defined functions or builtins
+    repl.run("sq(x) = x * x").unwrap();
+    repl.run("even(x) = x % 2 == 0").unwrap();
+    repl.run("add(a, b) = a + b").unwrap();
+    assert_eq!(show(&mut repl, "map(sq, (1, 2, 3))"), "(1, 4, 9)");
+    assert_eq!(show(&mut repl, "map(abs, { 1 = -1, 2 = -2 })"), "(1, 2)");
+    assert_eq!(show(&mut repl, "filter(even, range(0, 7))"), "(0, 2, 4, 6)");
+    expect!(repl, "fold(add, 0, range(1, 101))", lovm::Value::I64(5050));
+    expect!(repl, "len(map(sq, ()))", lovm::Value::I64(0));
+    // every call pushes into its own tuple
+    assert_eq!(
+        show(&mut repl, "(map(sq, (1, 2)), map(sq, (3, 4)))"),
+        "((1, 4), (9, 16))"
+    );
+    assert_eq!(
+        show(&mut repl, "values({ 1 = map(sq, (1, 2)) })"),
+        "((1, 4))"
+    );
+
+    // helpers also work inside functions and run as loops
+    repl.run("total(xs) = fold(add, 0, map(sq, xs))").unwrap();
+    expect!(repl, "total((1, 2, 3))", lovm::Value::I64(14));
+    expect!(
+        repl,
+        "len(filter(even, range(0, 10000)))",
+        lovm::Value::I64(5000)
+    );
+    assert!(repl
+        .runtime
+        .functions()
+        .iter()
+        .all(|name| !name.contains('@')));
+    assert!(repl.run("filter(sq, (1, 2))").is_err());
+
+    // callbacks are resolved by name, so passing a function through a parameter is rejected
+    repl.run("g(x) = x + 100").unwrap();
+    assert!(repl.run("apply(g, xs) = map(g, xs)").is_err());
+    assert!(repl.run("apply(h, xs) = fold(h, 0, xs)").is_err());
+    repl.run("apply(xs) = map(g, xs)").unwrap();
+    assert_eq!(show(&mut repl, "apply((1, 2))"), "(101, 102)");
+}
+
 //#[test]
 //fn test_stdlib() {
 //    let mut repl = Repl::with_stdlib();